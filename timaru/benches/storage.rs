@@ -0,0 +1,104 @@
+//! Load time and size of a year of schedules in the text and binary formats.
+//!
+//! Run with `cargo bench --bench storage`.
+
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use chrono::{Duration as Days, Local, TimeZone};
+
+use timaru::{
+    error::Error,
+    parser::BINARY_MAGIC,
+    schedule::Schedule,
+    storage,
+    task::{Priority, Subtask, Task, TaskTime, TimeOfDay},
+};
+
+const RUNS: u32 = 20;
+
+/// Fills `db_dir` with a schedule of 7 tasks for every day of 2026, six periods with pomodoro,
+/// a subtask and a note, and one in the evening.
+async fn generate(db_dir: &Path) -> Result<(), Error> {
+    let first = Local.ymd(2026, 1, 1);
+    for day in 0..365 {
+        let date = first + Days::days(day);
+        let mut schedule = Schedule::open(db_dir, date).await?;
+        for hour in 8..14 {
+            let mut task = Task::new(
+                TaskTime::Period {
+                    start: date.and_hms(hour, 0, 0),
+                    end: date.and_hms(hour, 45, 0),
+                },
+                format!("work on project {} and write notes about it", hour),
+            );
+            task.pomodoro = Some((4, 2));
+            task.priority = Some(Priority::Medium);
+            task.subtasks.push(Subtask {
+                description: "review".into(),
+                finished: hour % 2 == 0,
+            });
+            task.notes.push("some context for the task".into());
+            schedule.add_task(task).unwrap();
+        }
+        schedule.add_task(Task::new(
+            TaskTime::General {
+                time: TimeOfDay::Evening,
+            },
+            "read".into(),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Average time to read and parse every schedule of `db_dir`, and their total size.
+async fn load(db_dir: &Path) -> Result<(Duration, u64), Error> {
+    let paths: Vec<_> = Schedule::stored_dates(db_dir)
+        .await?
+        .iter()
+        .map(|date| Schedule::path(db_dir, date))
+        .collect();
+    let size = paths
+        .iter()
+        .map(|path| fs::metadata(path).map(|metadata| metadata.len()))
+        .sum::<Result<u64, _>>()?;
+
+    let mut total = Duration::default();
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let mut schedules = Vec::with_capacity(paths.len());
+        for path in paths.iter() {
+            let bytes = storage::decode(path, fs::read(path)?)?;
+            schedules.push(if bytes.starts_with(BINARY_MAGIC) {
+                Schedule::from_bytes(path.clone(), &bytes)?
+            } else {
+                Schedule::from_str(path.clone(), &String::from_utf8_lossy(&bytes))?
+            });
+        }
+        total += start.elapsed();
+        // Schedules are written back when dropped, which is not part of loading them.
+        drop(schedules);
+    }
+
+    Ok((total / RUNS, size))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let db_dir = std::env::temp_dir().join(format!("timaru-bench-{}", std::process::id()));
+    fs::create_dir_all(&db_dir)?;
+    generate(&db_dir).await?;
+
+    println!("{:<8} {:>12} {:>12}", "format", "load time", "size");
+    for (name, binary) in [("text", false), ("binary", true)].iter() {
+        storage::convert(&db_dir, *binary).await?;
+        let (time, size) = load(&db_dir).await?;
+        println!("{:<8} {:>12.2?} {:>10} kB", name, time, size / 1000);
+    }
+
+    fs::remove_dir_all(&db_dir)?;
+    Ok(())
+}
@@ -0,0 +1,370 @@
+//! Backups of the config directory, made with `timaru backup` and restored with `timaru restore`.
+//!
+//! A backup is a gzipped tar archive of the files of the config directory, i.e. the config file
+//! and the schedules of `db/`, but not the git history of `db/`. The `MANIFEST.json` at its root
+//! lists every file along with its size and SHA-256 checksum, and a `<archive>.sha256` file next
+//! to the archive holds the checksum of the archive itself, as written by `sha256sum`.
+//!
+//! Every file of a backup is checked against the manifest before anything is restored. Restoring
+//! replaces the schedules with the ones of the backup, unless they are merged, in which case the
+//! tasks of the backup which are missing or changed later than the ones in the database are
+//! copied, and the other files of the backup are only restored if they do not exist.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs as std_fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use chrono::Local;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::{error::Error, format::formats, parser::TEXT_VERSION, storage, sync};
+
+const MANIFEST: &str = "MANIFEST.json";
+/// Files of `db/` describing the database itself rather than holding schedules.
+const DB_FILES: &[&str] = &["db/ENCRYPTION", "db/FORMAT", "db/VERSION"];
+
+/// Contents of files, along with their paths relative to the config directory.
+type Files = Vec<(String, Vec<u8>)>;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// Version of timaru which made the backup.
+    timaru: String,
+    created: String,
+    /// Version of the database, see `storage::version`.
+    db_version: u32,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Path of the file holding the checksum of `archive`.
+fn checksum_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".sha256");
+    PathBuf::from(path)
+}
+
+/// Reads the files in `dir`, along with their paths relative to `root`, leaving out the git
+/// history of the database.
+fn read_files(root: &Path, dir: &Path, files: &mut Files) -> Result<(), Error> {
+    for entry in std_fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if relative == Path::new("db/.git") {
+            continue;
+        }
+
+        if path.is_dir() {
+            read_files(root, &path, files)?;
+        } else if path.is_file() {
+            let name = relative
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((name, std_fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a backup of `cfg_dir` to `output`, and its checksum next to it. Returns the checksum.
+pub async fn backup(cfg_dir: &Path, output: &Path) -> Result<String, Error> {
+    let mut files = Vec::new();
+    read_files(cfg_dir, cfg_dir, &mut files)?;
+    // A previous backup written to the config directory is not part of the next one.
+    files.retain(|(name, _)| {
+        cfg_dir.join(name) != output && cfg_dir.join(name) != checksum_path(output)
+    });
+    files.sort();
+
+    let manifest = Manifest {
+        timaru: env!("CARGO_PKG_VERSION").to_string(),
+        created: Local::now().to_rfc3339(),
+        db_version: storage::version(&cfg_dir.join("db")).await?,
+        files: files
+            .iter()
+            .map(|(path, content)| ManifestFile {
+                path: path.clone(),
+                size: content.len() as u64,
+                sha256: sha256(content),
+            })
+            .collect(),
+    };
+
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    for (path, content) in std::iter::once((MANIFEST, &manifest))
+        .chain(files.iter().map(|(path, content)| (path.as_str(), content)))
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(Local::now().timestamp() as u64);
+        archive.append_data(&mut header, path, content.as_slice())?;
+    }
+    let archive = archive.into_inner()?.finish()?;
+
+    let checksum = sha256(&archive);
+    fs::write(output, &archive).await?;
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(checksum_path(output), format!("{}  {}\n", checksum, name)).await?;
+    Ok(checksum)
+}
+
+/// Reads the files of the backup `archive`, checking them against its manifest.
+fn read_archive(archive: &[u8]) -> Result<(Manifest, Files), Error> {
+    let mut manifest = None;
+    let mut files = Vec::new();
+
+    let invalid = |_| Error::Backup("not a gzipped tar archive".into());
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(invalid)?.to_path_buf();
+        if !path
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            return Err(Error::Backup(format!("invalid path {}", path.display())));
+        }
+
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(invalid)?;
+        match path.to_str() {
+            Some(MANIFEST) => manifest = Some(serde_json::from_slice::<Manifest>(&content)?),
+            Some(path) => files.push((path.to_string(), content)),
+            None => return Err(Error::Backup(format!("invalid path {}", path.display()))),
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| Error::Backup("no manifest".into()))?;
+    let listed: BTreeSet<_> = manifest.files.iter().map(|file| &file.path).collect();
+    let contents: HashMap<_, _> = files
+        .iter()
+        .map(|(path, content)| (path, content))
+        .collect();
+    for file in manifest.files.iter() {
+        match contents.get(&file.path) {
+            Some(content)
+                if content.len() as u64 == file.size && sha256(content) == file.sha256 => {}
+            Some(_) => return Err(Error::Backup(format!("{} is corrupted", file.path))),
+            None => return Err(Error::Backup(format!("{} is missing", file.path))),
+        }
+    }
+    if let Some((path, _)) = files.iter().find(|(path, _)| !listed.contains(path)) {
+        return Err(Error::Backup(format!("{} is not in the manifest", path)));
+    }
+
+    Ok((manifest, files))
+}
+
+/// Restores the backup `archive` to `cfg_dir`, replacing the schedules of the database, or
+/// merging the tasks of the backup into them if `merge` is set.
+pub async fn restore(cfg_dir: &Path, archive: &Path, merge: bool) -> Result<(), Error> {
+    let bytes = fs::read(archive).await?;
+    if let Ok(checksum) = fs::read_to_string(checksum_path(archive)).await {
+        if checksum.split_whitespace().next() != Some(&sha256(&bytes)) {
+            return Err(Error::Backup(
+                "checksum of the archive does not match".into(),
+            ));
+        }
+    }
+    let (manifest, files) = read_archive(&bytes)?;
+    if manifest.db_version > TEXT_VERSION {
+        return Err(Error::NewerVersion(manifest.db_version));
+    }
+
+    let db_dir = cfg_dir.join("db");
+    if !merge {
+        return replace_db(cfg_dir, files).await;
+    }
+
+    let backup_dir = std::env::temp_dir().join(format!("timaru-restore-{}", std::process::id()));
+    let merged = async {
+        for (path, content) in files.iter() {
+            let schedule = path.starts_with("db/") && path[3..].starts_with(char::is_numeric);
+            if schedule {
+                write(&backup_dir.join(path), content).await?;
+            } else if !DB_FILES.contains(&path.as_str()) && !cfg_dir.join(path).exists() {
+                write(&cfg_dir.join(path), content).await?;
+            }
+        }
+        merge_tasks(&db_dir, &backup_dir.join("db")).await
+    }
+    .await;
+    if backup_dir.exists() {
+        fs::remove_dir_all(&backup_dir).await?;
+    }
+    merged
+}
+
+/// Replaces the schedules of `cfg_dir` with the ones of `files`, keeping the history so that
+/// restoring shows up in it. The schedules are written to a new directory which then takes the
+/// place of `db/`, so that they are left as they were if writing fails.
+async fn replace_db(cfg_dir: &Path, files: Files) -> Result<(), Error> {
+    let db_dir = cfg_dir.join("db");
+    let new_dir = cfg_dir.join("db.restore");
+    let old_dir = cfg_dir.join("db.old");
+    for dir in [&new_dir, &old_dir].iter() {
+        if dir.exists() {
+            fs::remove_dir_all(dir).await?;
+        }
+    }
+
+    let mut other_files = Vec::new();
+    let written = async {
+        fs::create_dir_all(&new_dir).await?;
+        for (path, content) in files {
+            match path.strip_prefix("db/") {
+                Some(db_path) => write(&new_dir.join(db_path), &content).await?,
+                None => other_files.push((path, content)),
+            }
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(err) = written {
+        fs::remove_dir_all(&new_dir).await?;
+        return Err(err);
+    }
+
+    if db_dir.join(".git").exists() {
+        fs::rename(db_dir.join(".git"), new_dir.join(".git")).await?;
+    }
+    if db_dir.exists() {
+        fs::rename(&db_dir, &old_dir).await?;
+    }
+    fs::rename(&new_dir, &db_dir).await?;
+    if old_dir.exists() {
+        fs::remove_dir_all(&old_dir).await?;
+    }
+
+    for (path, content) in other_files {
+        write(&cfg_dir.join(path), &content).await?;
+    }
+    Ok(())
+}
+
+async fn write(path: &Path, content: &[u8]) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Copies the tasks of `backup_dir` which are missing from `db_dir`, or which were changed later
+/// than their copy in `db_dir`.
+async fn merge_tasks(db_dir: &Path, backup_dir: &Path) -> Result<(), Error> {
+    if !backup_dir.exists() {
+        return Ok(());
+    }
+    let local_tasks = sync::tasks_by_id(db_dir).await?;
+
+    for (id, (date, task)) in sync::tasks_by_id(backup_dir).await? {
+        let local = local_tasks.get(&id);
+        let action = match local {
+            None => "add",
+            Some((_, local)) if task.modified > local.modified => "update",
+            Some(_) => continue,
+        };
+        println!(
+            "{} {} {} {}",
+            action,
+            formats().date(date.naive_local()),
+            id,
+            task.description
+        );
+        sync::put_task(db_dir, local.map(|(date, _)| *date), date, task).await?;
+    }
+    Ok(())
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_backup_round_trip() {
+        use std::env;
+
+        use tokio::fs;
+
+        use super::{backup, read_archive, restore};
+
+        let dir = env::temp_dir().join(format!("timaru-test-backup-{}", std::process::id()));
+        let cfg_dir = dir.join("timaru");
+        fs::create_dir_all(cfg_dir.join("db/2026/10"))
+            .await
+            .unwrap();
+        fs::create_dir_all(cfg_dir.join("db/.git")).await.unwrap();
+        fs::write(cfg_dir.join("db/.git/HEAD"), "ref: refs/heads/master\n")
+            .await
+            .unwrap();
+        fs::write(cfg_dir.join("config.toml"), "[history]\ngit = true\n")
+            .await
+            .unwrap();
+        let schedule = "# 19-10-2026 v2\n* [ ] 9:00 {id:1} => write report\n";
+        fs::write(cfg_dir.join("db/2026/10/19"), schedule)
+            .await
+            .unwrap();
+
+        let archive = dir.join("backup.tar.gz");
+        let checksum = backup(&cfg_dir, &archive).await.unwrap();
+        let bytes = fs::read(&archive).await.unwrap();
+        let (manifest, files) = read_archive(&bytes).unwrap();
+        // The history of the database is left out.
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(files[1].0, "db/2026/10/19");
+        assert!(fs::read_to_string(dir.join("backup.tar.gz.sha256"))
+            .await
+            .unwrap()
+            .starts_with(&checksum));
+
+        fs::write(cfg_dir.join("db/2026/10/19"), "").await.unwrap();
+        fs::write(cfg_dir.join("db/2026/10/20"), "# 20-10-2026 v2\n")
+            .await
+            .unwrap();
+        restore(&cfg_dir, &archive, false).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(cfg_dir.join("db/2026/10/19"))
+                .await
+                .unwrap(),
+            schedule
+        );
+        assert!(!cfg_dir.join("db/2026/10/20").exists());
+        assert!(cfg_dir.join("db/.git/HEAD").exists());
+        assert!(!cfg_dir.join("db.restore").exists());
+        assert!(!cfg_dir.join("db.old").exists());
+
+        // A corrupted archive is refused before anything is restored.
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 20;
+        corrupted[last] ^= 0xff;
+        assert!(read_archive(&corrupted).is_err());
+        fs::write(&archive, corrupted).await.unwrap();
+        assert!(restore(&cfg_dir, &archive, false).await.is_err());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
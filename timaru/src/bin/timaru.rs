@@ -1,11 +1,25 @@
+use std::env;
+
 use clap::Clap;
-use tracing_subscriber;
 
-use timaru::{cli::Opts, error::Error, setup::check_setup, tui::TimaruTui};
+use timaru::{
+    cli::Opts, complete, config::Config, error::Error, format, history, setup::check_setup,
+    storage, tui::TimaruTui,
+};
 
 async fn run() -> Result<(), Error> {
     tracing_subscriber::fmt::init();
     let (_cfg_dir, db_dir) = check_setup().await?;
+    let config = Config::load().await?;
+    format::init(&config.format)?;
+    history::init(&db_dir, &config.history);
+
+    let args: Vec<_> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(complete::HOOK) {
+        complete::run(&db_dir, &args[1..]).await;
+        return Ok(());
+    }
+    storage::init(&db_dir).await?;
 
     let opts = Opts::parse();
     match opts.subcmd {
@@ -1,13 +1,32 @@
-use std::path::Path;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use chrono::{Datelike, Duration, Local, TimeZone};
+use chrono::{Date, Datelike, Duration, Local, TimeZone};
 use clap::Clap;
+use tokio::fs;
 
 use crate::{
+    backup, complete,
+    config::Config,
+    editor,
     error::Error,
-    parser::get_date,
+    export,
+    format::formats,
+    fsck,
+    heatmap::Heatmap,
+    history,
+    parser::TEXT_VERSION,
     schedule::Schedule,
-    task::{Task, TaskTime},
+    serve,
+    setup::config_dir,
+    shell,
+    stats::Stats,
+    storage, sync,
+    task::{Priority, Subtask, Task, TaskTime, WorkInterval, Zone},
 };
 
 #[derive(Clap, Debug, Clone)]
@@ -34,6 +53,19 @@ pub enum SubCommand {
         /// Whether to enable pomodoro for this task or not
         #[clap(long, short)]
         pomodoro: Option<u8>,
+        /// The priority of the task, one of `A`, `B` or `C` (`A` being the highest)
+        #[clap(long, short = 'P')]
+        priority: Option<String>,
+        /// Ids of the tasks which have to be finished before this task
+        #[clap(long, short, number_of_values = 1)]
+        after: Vec<String>,
+        /// The deadline of the task, as `dd-mm-yyyy` optionally followed by `@hh:mm`
+        #[clap(long, short = 'D')]
+        due: Option<String>,
+        /// The timezone of the time of the task, like `Europe/Paris` or `+02:00`. Tasks without one
+        /// follow the local time.
+        #[clap(long)]
+        tz: Option<String>,
         /// The task description
         description: String,
     },
@@ -55,7 +87,132 @@ pub enum SubCommand {
         subcmd: UpdateSubCmd,
     },
     /// View a particular day's schedule. If no argument is provided shows current day's schedule.
-    List { date: Option<String> },
+    List {
+        date: Option<String>,
+        /// Show the times of tasks in this timezone, like `Asia/Tokyo` or `+09:00`, instead of the
+        /// local one
+        #[clap(long)]
+        tz: Option<String>,
+    },
+    /// Start working on a task, stopping the task being worked on if any
+    Start {
+        /// The date of the task
+        date: String,
+        /// The index of the task
+        idx: u8,
+    },
+    /// Stop working on the task being worked on
+    Stop,
+    /// Search descriptions, subtasks and notes of tasks on all days
+    Search { pattern: String },
+    /// Export schedules to other formats
+    Export {
+        #[clap(subcommand)]
+        format: ExportFormat,
+    },
+    /// Import tasks from other formats
+    Import {
+        #[clap(subcommand)]
+        format: ImportFormat,
+    },
+    /// Print statistics of the schedules in the given range
+    Stats {
+        /// One of `week`, `month`, `year` or `all`, counted back from today
+        #[clap(long, short, default_value = "week")]
+        range: String,
+        /// Print the statistics as JSON
+        #[clap(long, short)]
+        json: bool,
+    },
+    /// Print a heatmap of finished tasks on each day of a year
+    Heatmap {
+        /// The year to print, the current year if not provided
+        #[clap(long, short)]
+        year: Option<i32>,
+        /// Count done pomodoros instead of finished tasks
+        #[clap(long, short)]
+        pomodoro: bool,
+        /// Write the heatmap as an SVG image to this file instead of printing it
+        #[clap(long, short)]
+        svg: Option<PathBuf>,
+    },
+    /// Print the chain of tasks a task depends on. If no id is provided, prints all chains.
+    Graph { id: Option<String> },
+    /// Serve the schedules to other applications on localhost
+    Serve {
+        #[clap(subcommand)]
+        mode: ServeMode,
+    },
+    /// Sync the schedules with a remote copy, a directory or a WebDAV URL
+    Sync {
+        /// The remote to sync with, the `remote` of the `[sync]` config section if not provided
+        remote: Option<String>,
+        /// Only print what would be pulled, pushed or deleted
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+        /// Resolve the conflict on the task with this id by keeping the local copy
+        #[clap(long, number_of_values = 1)]
+        keep_local: Vec<String>,
+        /// Resolve the conflict on the task with this id by keeping the remote copy
+        #[clap(long, number_of_values = 1)]
+        keep_remote: Vec<String>,
+    },
+    /// Print the changes to the schedule of a date, or to all schedules, if history is enabled
+    History { date: Option<String> },
+    /// Write a backup of the config file and the schedules to a compressed archive
+    Backup {
+        /// The archive to write, `timaru-<date>.tar.gz` in the current directory if not provided
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Restore the schedule of a date or all schedules as they were at a revision of history, or
+    /// a backup with `--backup`
+    Restore {
+        /// The revision to restore, as printed by `history`, or the backup archive written by
+        /// `backup` with `--backup`
+        source: String,
+        /// The date of the schedule to restore from history
+        date: Option<String>,
+        /// Restore the backup archive `source`
+        #[clap(long, short)]
+        backup: bool,
+        /// Merge the tasks of the backup into the schedules instead of replacing them
+        #[clap(long, short, requires = "backup")]
+        merge: bool,
+    },
+    /// Encrypt the schedules with a new passphrase
+    Encrypt,
+    /// Decrypt the schedules, storing them as plain text again
+    Decrypt,
+    /// Convert the schedules to another storage format, used for schedules written from now on
+    Convert {
+        /// One of `text` or `binary`
+        format: String,
+    },
+    /// Upgrade the schedules written by older versions to the current format
+    Migrate,
+    /// Check that every schedule can be read, and repair the ones which cannot
+    Fsck {
+        /// Repair every schedule which can be repaired
+        #[clap(long)]
+        fix: bool,
+        /// Ask before repairing each schedule
+        #[clap(long, short)]
+        interactive: bool,
+    },
+    /// List unfinished tasks which are overdue or due in the next few days
+    Due {
+        /// Number of days ahead to look for deadlines
+        #[clap(long, short, default_value = "7")]
+        within: i64,
+    },
+    /// Print the completion script of a shell
+    Completions {
+        /// One of `bash`, `zsh` or `fish`
+        shell: String,
+    },
+    /// Start an interactive shell running commands on a current date
+    Shell,
 }
 
 #[derive(Clap, Debug, Clone)]
@@ -73,8 +230,31 @@ pub enum UpdateSubCmd {
     Description {
         desc: String,
     },
+    /// Change the priority of the task. If no priority is provided, removes it.
+    Priority {
+        priority: Option<String>,
+    },
+    /// Add, remove or check subtasks of the task
+    Subtask(SubtaskUpdate),
+    /// Edit the notes of the task in $EDITOR
+    Notes,
+    /// Change the deadline of the task. If no deadline is provided, removes it.
+    Due {
+        due: Option<String>,
+    },
+    /// Change the timezone of the task, keeping its times on the clocks. If no timezone is
+    /// provided, the task follows the local time.
+    Tz {
+        tz: Option<String>,
+    },
+    /// Add or remove tasks this task depends on
+    Depend(DependUpdate),
     /// Mark task as completed
-    Done,
+    Done {
+        /// Mark the task as done even if tasks it depends on are not finished
+        #[clap(long, short)]
+        force: bool,
+    },
     /// Mark task as incomplete
     NotDone,
 }
@@ -86,15 +266,228 @@ pub enum PomodoroUpdate {
     Done { done: u8 },
 }
 
+#[derive(Clap, Debug, Clone)]
+pub enum SubtaskUpdate {
+    /// Add a new subtask
+    Add { desc: String },
+    /// Remove a subtask
+    Remove { idx: usize },
+    /// Mark subtask as completed
+    Done { idx: usize },
+    /// Mark subtask as incomplete
+    NotDone { idx: usize },
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum ExportFormat {
+    /// Export as CSV, with one row per task
+    Csv(ExportRange),
+    /// Export as a self-contained HTML page with a calendar grid
+    Html {
+        #[clap(flatten)]
+        range: ExportRange,
+        /// One of `week`, for a single grid of weeks, or `month`, for a grid for each month
+        #[clap(long, short, default_value = "week")]
+        view: String,
+    },
+    /// Export as a markdown agenda
+    Markdown(ExportRange),
+    /// Export as an org-mode file
+    Org(ExportRange),
+    /// Export as todo.txt, with one line per task
+    TodoTxt(ExportRange),
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum ServeMode {
+    /// Serve the schedules as a CalDAV calendar of tasks
+    Caldav {
+        /// The port to listen on
+        #[clap(long, short, default_value = "5232")]
+        port: u16,
+    },
+    /// Serve a JSON API to list and change schedules
+    Http {
+        /// The port to listen on
+        #[clap(long, short, default_value = "8080")]
+        port: u16,
+    },
+}
+
+/// Dates to export, all stored schedules being exported if neither is provided.
+#[derive(Clap, Debug, Clone)]
+pub struct ExportRange {
+    /// The first date to export
+    #[clap(long, short)]
+    from: Option<String>,
+    /// The last date to export
+    #[clap(long, short)]
+    to: Option<String>,
+    /// The file to write to, instead of printing
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum ImportFormat {
+    /// Import CSV in the format of `export csv`, updating tasks with matching ids
+    Csv {
+        file: PathBuf,
+        /// Only print which tasks would be created or updated
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+    /// Import todo.txt in the format of `export todo-txt`, updating tasks with matching ids
+    TodoTxt {
+        file: PathBuf,
+        /// Only print which tasks would be created or updated
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+}
+
+/// The first and last dates of an `ExportRange`, if provided.
+type DateRange = (Option<Date<Local>>, Option<Date<Local>>);
+
+impl ExportRange {
+    /// The first and last dates of the range, if provided.
+    fn dates(&self) -> Result<DateRange, Error> {
+        Ok((
+            self.from.as_deref().map(parse_date).transpose()?,
+            self.to.as_deref().map(parse_date).transpose()?,
+        ))
+    }
+
+    /// Stored schedules within the range.
+    async fn schedules(&self, db_dir: &Path) -> Result<Vec<Schedule>, Error> {
+        let (from, to) = self.dates()?;
+
+        let mut schedules = Vec::new();
+        for date in Schedule::stored_dates(db_dir).await? {
+            if from.map(|from| from <= date) != Some(false)
+                && to.map(|to| date <= to) != Some(false)
+            {
+                schedules.push(Schedule::open(db_dir, date).await?);
+            }
+        }
+        Ok(schedules)
+    }
+
+    async fn write(&self, content: &str) -> Result<(), Error> {
+        match &self.output {
+            Some(file) => fs::write(file, content).await?,
+            None => print!("{}", content),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum DependUpdate {
+    /// Make the task depend on the task with the given id
+    Add { id: String },
+    /// Remove the dependency on the task with the given id
+    Remove { id: String },
+}
+
+/// Adds the lines of the tree of the tasks `id` depends on to `lines`, each line starting with
+/// `prefix`. A task already in `path`, the chain from the root, is marked as a cycle.
+fn dependency_lines(
+    id: &str,
+    tasks: &HashMap<&str, &(Date<Local>, u8, Task)>,
+    prefix: &str,
+    path: &mut Vec<String>,
+    lines: &mut Vec<String>,
+) {
+    let dependencies = match tasks.get(id) {
+        Some((_, _, task)) => &task.dependencies,
+        None => return,
+    };
+
+    path.push(id.to_string());
+    for (i, dependency) in dependencies.iter().enumerate() {
+        let last = i + 1 == dependencies.len();
+        let line = match tasks.get(dependency.as_str()) {
+            Some((date, idx, task)) => format!(
+                "{} {} ({}) {}",
+                dependency,
+                formats().date(date.naive_local()),
+                idx,
+                task.header_as_string_with(None, &formats())
+            ),
+            None => format!("{} (missing)", dependency),
+        };
+        lines.push(format!(
+            "{}{} {}",
+            prefix,
+            if last { "└─" } else { "├─" },
+            line
+        ));
+
+        if path.contains(dependency) {
+            lines.push(format!("{}   (cycle)", prefix));
+        } else {
+            let prefix = format!("{}{}", prefix, if last { "   " } else { "│  " });
+            dependency_lines(dependency, tasks, &prefix, path, lines);
+        }
+    }
+    path.pop();
+}
+
+/// Reads a date given on the command line, in the configured format or the canonical one.
+fn parse_date(input: &str) -> Result<Date<Local>, Error> {
+    formats().parse_date(input)
+}
+
 impl SubCommand {
+    /// Runs the command. Commands changing many schedules at once commit all of their changes to
+    /// the history together, while the others commit each schedule they write.
     pub async fn parse(self, db_dir: &Path) -> Result<(), Error> {
+        match self.history_message() {
+            Some(message) => history::batch(db_dir, &message, self.run(db_dir)).await,
+            None => self.run(db_dir).await,
+        }
+    }
+
+    /// The message of the history commit of commands changing many schedules at once, or `None`
+    /// for other commands.
+    fn history_message(&self) -> Option<String> {
+        let message = match self {
+            SubCommand::Import { format } => match format {
+                ImportFormat::Csv { file, dry_run } | ImportFormat::TodoTxt { file, dry_run } => {
+                    if *dry_run {
+                        return None;
+                    }
+                    format!("import {}", file.display())
+                }
+            },
+            SubCommand::Sync { dry_run: false, .. } => "sync".to_string(),
+            SubCommand::Encrypt => "encrypt schedules".to_string(),
+            SubCommand::Decrypt => "decrypt schedules".to_string(),
+            SubCommand::Convert { format } => format!("convert schedules to {}", format),
+            SubCommand::Restore {
+                source,
+                backup: true,
+                ..
+            } => format!("restore backup {}", source),
+            SubCommand::Migrate => format!("migrate schedules to version {}", TEXT_VERSION),
+            SubCommand::Fsck { fix, interactive } if *fix || *interactive => {
+                "repair schedules".to_string()
+            }
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    async fn run(self, db_dir: &Path) -> Result<(), Error> {
         match self {
             SubCommand::Week => {
                 let today = Local::today();
-                Schedule::open_range(db_dir, today, today + Duration::days(7))
+                let blocked = Schedule::blocked_tasks(db_dir).await?;
+                Schedule::read_range(db_dir, today, today + Duration::days(7))
                     .await?
                     .iter()
-                    .for_each(|schedule| println!("{:?}", schedule));
+                    .for_each(|schedule| println!("{}", schedule.listing(&blocked)));
             }
             SubCommand::Month => {
                 let today = Local::today();
@@ -104,44 +497,366 @@ impl SubCommand {
                     1
                 };
                 let next_month_day = Local.ymd(today.year(), next_month, today.day());
-                Schedule::open_range(db_dir, today, next_month_day)
+                let blocked = Schedule::blocked_tasks(db_dir).await?;
+                Schedule::read_range(db_dir, today, next_month_day)
                     .await?
                     .iter()
-                    .for_each(|schedule| println!("{:?}", schedule));
+                    .for_each(|schedule| println!("{}", schedule.listing(&blocked)));
             }
-            SubCommand::List { date } => {
+            SubCommand::List { date, tz } => {
                 let date = match date {
-                    Some(date_string) => get_date(&date_string)?,
+                    Some(date_string) => parse_date(&date_string)?,
                     None => Local::today(),
                 };
-                println!("{:?}", Schedule::open(&db_dir, date).await?);
+                let tz = tz.as_deref().map(Zone::from_str).transpose()?;
+                let blocked = Schedule::blocked_tasks(db_dir).await?;
+                let schedule = Schedule::read(db_dir, date).await?;
+                println!("{}", schedule.listing_in(&blocked, tz.as_ref()));
+            }
+            SubCommand::Start { date, idx } => {
+                let now = Local::now();
+                let date = parse_date(&date)?;
+                Schedule::stop_tracking(db_dir, now).await?;
+
+                match Schedule::open(db_dir, date).await?.tasks.get_mut(&idx) {
+                    Some(task) => task.intervals.push(WorkInterval {
+                        start: now,
+                        end: None,
+                    }),
+                    None => return Err(Error::Idx),
+                }
+            }
+            SubCommand::Stop => {
+                if !Schedule::stop_tracking(db_dir, Local::now()).await? {
+                    return Err(Error::NotTracking);
+                }
+            }
+            SubCommand::Search { pattern } => {
+                for date in Schedule::stored_dates(db_dir).await? {
+                    let schedule = Schedule::open(db_dir, date).await?;
+                    let matches: Vec<_> = schedule
+                        .sorted_tasks()
+                        .into_iter()
+                        .filter(|(_, task)| task.matches(&pattern))
+                        .collect();
+
+                    if !matches.is_empty() {
+                        println!("# {}", formats().date(date.naive_local()));
+                        for (idx, task) in matches {
+                            println!(
+                                "({}) {}{}",
+                                idx,
+                                task.header_as_string_with(None, &formats()),
+                                task.children_as_string()
+                            );
+                        }
+                    }
+                }
+            }
+            SubCommand::Export { format } => match format {
+                ExportFormat::Csv(range) => {
+                    let schedules = range.schedules(db_dir).await?;
+                    let tasks = schedules.iter().flat_map(|schedule| {
+                        schedule
+                            .sorted_tasks()
+                            .into_iter()
+                            .map(move |(_, task)| (&schedule.date, task))
+                    });
+                    range.write(&export::tasks_as_csv(tasks)).await?;
+                }
+                ExportFormat::Html { range, view } => {
+                    let view = export::HtmlView::from_str(&view)?;
+                    let schedules = range.schedules(db_dir).await?;
+                    let (from, to) = range.dates()?;
+                    range
+                        .write(&export::schedules_as_html(&schedules, from, to, view))
+                        .await?;
+                }
+                ExportFormat::Markdown(range) => {
+                    let schedules = range.schedules(db_dir).await?;
+                    range
+                        .write(&export::schedules_as_markdown(&schedules))
+                        .await?;
+                }
+                ExportFormat::Org(range) => {
+                    let schedules = range.schedules(db_dir).await?;
+                    range.write(&export::schedules_as_org(&schedules)).await?;
+                }
+                ExportFormat::TodoTxt(range) => {
+                    let schedules = range.schedules(db_dir).await?;
+                    let tasks = schedules.iter().flat_map(|schedule| {
+                        schedule
+                            .sorted_tasks()
+                            .into_iter()
+                            .map(move |(_, task)| (&schedule.date, task))
+                    });
+                    range.write(&export::tasks_as_todo_txt(tasks)).await?;
+                }
+            },
+            SubCommand::Import { format } => match format {
+                ImportFormat::Csv { file, dry_run } => {
+                    let rows = export::rows_from_csv(&fs::read_to_string(file).await?)?;
+                    export::import_csv_rows(db_dir, rows, dry_run).await?;
+                }
+                ImportFormat::TodoTxt { file, dry_run } => {
+                    let input = fs::read_to_string(file).await?;
+                    export::import_todo_txt(db_dir, &input, dry_run).await?;
+                }
+            },
+            SubCommand::Stats { range, json } => {
+                let today = Local::today();
+                let start_date = match range.as_str() {
+                    "week" => today - Duration::days(6),
+                    "month" => today - Duration::days(29),
+                    "year" => today - Duration::days(364),
+                    "all" => match Schedule::stored_dates(db_dir).await?.first() {
+                        Some(date) => *date,
+                        None => today,
+                    },
+                    _ => return Err(Error::Parse("range")),
+                };
+
+                let stats = Stats::collect(db_dir, start_date, today).await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                } else {
+                    print!("{}", stats.as_string());
+                }
+            }
+            SubCommand::Heatmap {
+                year,
+                pomodoro,
+                svg,
+            } => {
+                let year = year.unwrap_or_else(|| Local::today().year());
+                let heatmap = Heatmap::collect(db_dir, year, pomodoro).await?;
+                match svg {
+                    Some(file) => fs::write(file, heatmap.as_svg()).await?,
+                    None => print!("{}", heatmap.as_terminal_string()),
+                }
+            }
+            SubCommand::Graph { id } => {
+                let tasks = Schedule::all_tasks(db_dir).await?;
+                let tasks_by_id: HashMap<_, _> =
+                    tasks.iter().map(|t| (t.2.id.as_str(), t)).collect();
+
+                let roots: Vec<&str> = match &id {
+                    Some(id) if tasks_by_id.contains_key(id.as_str()) => vec![id],
+                    Some(id) => return Err(Error::NoTask(id.clone())),
+                    // Only print chains from the tasks which no other task depends on.
+                    None => {
+                        let depended: HashSet<_> = tasks
+                            .iter()
+                            .flat_map(|(_, _, task)| task.dependencies.iter())
+                            .map(String::as_str)
+                            .collect();
+                        tasks
+                            .iter()
+                            .filter(|(_, _, task)| {
+                                !task.dependencies.is_empty()
+                                    && !depended.contains(task.id.as_str())
+                            })
+                            .map(|(_, _, task)| task.id.as_str())
+                            .collect()
+                    }
+                };
+
+                for root in roots {
+                    let (date, idx, task) = tasks_by_id[root];
+                    println!(
+                        "{} {} ({}) {}",
+                        root,
+                        formats().date(date.naive_local()),
+                        idx,
+                        task.header_as_string_with(None, &formats())
+                    );
+                    let mut lines = Vec::new();
+                    dependency_lines(root, &tasks_by_id, "", &mut Vec::new(), &mut lines);
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+            }
+            SubCommand::Serve { mode } => match mode {
+                ServeMode::Caldav { port } => {
+                    serve::serve_caldav(db_dir.to_path_buf(), port).await?;
+                }
+                ServeMode::Http { port } => {
+                    let token = Config::load().await?.http.token;
+                    serve::serve_http(db_dir.to_path_buf(), port, token).await?;
+                }
+            },
+            SubCommand::Sync {
+                remote,
+                dry_run,
+                keep_local,
+                keep_remote,
+            } => {
+                let remote = match remote {
+                    Some(remote) => remote,
+                    None => Config::load().await?.sync.remote.ok_or(Error::NoRemote)?,
+                };
+                let conflicts =
+                    sync::sync(db_dir, &remote, &keep_local, &keep_remote, dry_run).await?;
+                if !conflicts.is_empty() {
+                    println!(
+                        "{} conflicts left, resolve them with --keep-local <id> or \
+                         --keep-remote <id>",
+                        conflicts.len()
+                    );
+                }
+            }
+            SubCommand::History { date } => {
+                let date = date.as_deref().map(parse_date).transpose()?;
+                print!("{}", history::history(db_dir, date).await?);
+            }
+            SubCommand::Backup { output } => {
+                let output = output.unwrap_or_else(|| {
+                    PathBuf::from(format!("timaru-{}.tar.gz", Local::now().format("%Y-%m-%d")))
+                });
+                let checksum = backup::backup(&config_dir().await?, &output).await?;
+                println!("{}  {}", checksum, output.display());
+            }
+            SubCommand::Restore {
+                source,
+                date: None,
+                backup: true,
+                merge,
+            } => {
+                backup::restore(&config_dir().await?, Path::new(&source), merge).await?;
+            }
+            SubCommand::Restore { backup: true, .. } => {
+                return Err(Error::Backup("a backup is restored without a date".into()))
+            }
+            SubCommand::Restore { source, date, .. } => {
+                let date = date.as_deref().map(parse_date).transpose()?;
+                history::restore(db_dir, &source, date).await?;
+            }
+            SubCommand::Encrypt => storage::encrypt_db(db_dir).await?,
+            SubCommand::Decrypt => storage::decrypt_db(db_dir).await?,
+            SubCommand::Convert { format } => match format.as_str() {
+                "text" => storage::convert(db_dir, false).await?,
+                "binary" => storage::convert(db_dir, true).await?,
+                _ => return Err(Error::Parse("format should be `text` or `binary`")),
+            },
+            SubCommand::Completions { shell } => print!("{}", complete::script(&shell)?),
+            SubCommand::Shell => shell::run(db_dir).await?,
+            SubCommand::Migrate => match storage::migrate(db_dir).await? {
+                version if version < TEXT_VERSION => println!(
+                    "migrated schedules from version {} to {}",
+                    version, TEXT_VERSION
+                ),
+                _ => println!("schedules are up to date (version {})", TEXT_VERSION),
+            },
+            SubCommand::Fsck { fix, interactive } => {
+                let reports = fsck::check(db_dir).await?;
+                for report in reports.iter() {
+                    for problem in report.problems.iter() {
+                        println!("{}: {}", report.path.display(), problem);
+                    }
+                }
+
+                let repairable: Vec<_> = reports.iter().filter(|r| r.repair.is_some()).collect();
+                for report in repairable.iter() {
+                    let repair = report.repair.as_ref().unwrap().as_string();
+                    if interactive {
+                        print!("{}: {}? [y/N] ", report.path.display(), repair);
+                        io::stdout().flush()?;
+                        let mut answer = String::new();
+                        io::stdin().read_line(&mut answer)?;
+                        if !answer.trim().eq_ignore_ascii_case("y") {
+                            continue;
+                        }
+                    } else if !fix {
+                        continue;
+                    }
+                    fsck::repair(db_dir, report).await?;
+                    println!("{}: repaired, {}", report.path.display(), repair);
+                }
+
+                match (reports.len(), repairable.len()) {
+                    (0, _) => println!("no problems found"),
+                    (_, 0) => {}
+                    (_, n) if !fix && !interactive => {
+                        println!("{} schedule(s) can be repaired with `timaru fsck --fix`", n)
+                    }
+                    _ => {}
+                }
+            }
+            SubCommand::Due { within } => {
+                let now = Local::now();
+                let mut due_tasks = Vec::new();
+
+                for date in Schedule::stored_dates(db_dir).await? {
+                    let schedule = Schedule::open(db_dir, date).await?;
+                    for (idx, task) in schedule.tasks.iter() {
+                        if let (Some(deadline), false) = (&task.deadline, task.finished) {
+                            if deadline.as_datetime() - now < Duration::days(within) {
+                                due_tasks.push((deadline.as_datetime(), date, *idx, task.clone()));
+                            }
+                        }
+                    }
+                }
+
+                due_tasks.sort_by_key(|(due, date, idx, _)| (*due, *date, *idx));
+                for (_, date, idx, task) in due_tasks {
+                    println!(
+                        "{} ({}) {} ({})",
+                        formats().date(date.naive_local()),
+                        idx,
+                        task.header_as_string_with(None, &formats()),
+                        task.deadline.unwrap().countdown(&now)
+                    );
+                }
             }
             SubCommand::Add {
                 date,
                 time,
                 pomodoro,
+                priority,
+                after,
+                due,
+                tz,
                 description,
             } => {
                 let date = match date {
-                    Some(date_string) => get_date(&date_string)?,
+                    Some(date_string) => parse_date(&date_string)?,
                     None => Local::today(),
                 };
 
+                let ids: HashSet<_> = Schedule::all_tasks(db_dir)
+                    .await?
+                    .into_iter()
+                    .map(|(_, _, task)| task.id)
+                    .collect();
+                if let Some(id) = after.iter().find(|id| !ids.contains(*id)) {
+                    return Err(Error::NoTask(id.clone()));
+                }
+
+                let tz = tz.as_deref().map(Zone::from_str).transpose()?;
+                let time = match time {
+                    Some(time) => {
+                        TaskTime::from_str_in(&formats().canonical_time(&time), &date, tz.as_ref())?
+                    }
+                    None => TaskTime::Precise { time: Local::now() },
+                };
                 let task = Task {
-                    time: match time {
-                        Some(time) => TaskTime::from_str(&time, &date)?,
-                        None => TaskTime::Precise { time: Local::now() },
-                    },
-                    description,
                     pomodoro: pomodoro.map(|total| (total, 0)),
-                    finished: false,
+                    priority: priority.as_deref().map(Priority::from_str).transpose()?,
+                    deadline: due
+                        .as_deref()
+                        .map(|due| formats().parse_deadline(due))
+                        .transpose()?,
+                    dependencies: after,
+                    tz,
+                    ..Task::new(time, description)
                 };
-                Schedule::open(&db_dir, date).await?.add_task(task);
+                Schedule::open(db_dir, date).await?.add_task(task)?;
             }
             SubCommand::Remove { date, idx } => {
-                let date = get_date(&date)?;
+                let date = parse_date(&date)?;
 
-                if Schedule::open(&db_dir, date)
+                if Schedule::open(db_dir, date)
                     .await?
                     .remove_task(idx)
                     .is_none()
@@ -154,23 +869,40 @@ impl SubCommand {
                 idx,
                 subcmd,
             } => {
-                let old_date = get_date(&old_date)?;
-                let mut old_task_schedule = Schedule::open(&db_dir, old_date).await?;
+                let old_date = parse_date(&old_date)?;
+                let mut old_task_schedule = Schedule::open(db_dir, old_date).await?;
 
                 match subcmd {
                     UpdateSubCmd::Date { date } => {
-                        let date = get_date(&date)?;
-                        match old_task_schedule.remove_task(idx) {
-                            Some(mut task) => {
-                                task.time.change_date(&date);
-                                Schedule::open(&db_dir, date).await?.add_task(task);
+                        let date = parse_date(&date)?;
+                        // The task is only moved once its time exists on the new date and the new
+                        // schedule could be opened, so that it is never lost.
+                        let time = match old_task_schedule.tasks.get(&idx) {
+                            Some(task) => {
+                                let mut time = task.time.clone();
+                                time.change_date(&date, task.tz.as_ref())?;
+                                time
                             }
                             None => return Err(Error::Idx),
+                        };
+                        if date == old_date {
+                            old_task_schedule.tasks.get_mut(&idx).unwrap().time = time;
+                        } else {
+                            let mut schedule = Schedule::open(db_dir, date).await?;
+                            schedule.add_task(Task {
+                                time,
+                                ..old_task_schedule.tasks[&idx].clone()
+                            })?;
+                            old_task_schedule.remove_task(idx);
                         }
                     }
                     UpdateSubCmd::Time { time } => match old_task_schedule.tasks.get_mut(&idx) {
                         Some(task) => {
-                            task.time = TaskTime::from_str(&time, &old_task_schedule.date)?
+                            task.time = TaskTime::from_str_in(
+                                &formats().canonical_time(&time),
+                                &old_task_schedule.date,
+                                task.tz.as_ref(),
+                            )?
                         }
                         None => return Err(Error::Idx),
                     },
@@ -194,9 +926,10 @@ impl SubCommand {
                         }
                         PomodoroUpdate::Done { done } => {
                             match old_task_schedule.tasks.get_mut(&idx) {
-                                Some(task) => {
-                                    task.pomodoro = Some((task.pomodoro.unwrap().0, done))
-                                }
+                                Some(task) => match task.pomodoro {
+                                    Some((total, _)) => task.pomodoro = Some((total, done)),
+                                    None => return Err(Error::Parse("task has no pomodoro")),
+                                },
                                 None => return Err(Error::Idx),
                             }
                         }
@@ -205,12 +938,131 @@ impl SubCommand {
                             None => return Err(Error::Idx),
                         },
                     },
-                    UpdateSubCmd::Done => match old_task_schedule.tasks.get_mut(&idx) {
-                        Some(task) => task.finished = true,
+                    UpdateSubCmd::Priority { priority } => {
+                        let priority = priority.as_deref().map(Priority::from_str).transpose()?;
+                        match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => task.priority = priority,
+                            None => return Err(Error::Idx),
+                        }
+                    }
+                    UpdateSubCmd::Subtask(subtask_update) => {
+                        let task = match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => task,
+                            None => return Err(Error::Idx),
+                        };
+
+                        // Subtasks are listed starting from 1, just like tasks.
+                        match subtask_update {
+                            SubtaskUpdate::Add { desc } => task.subtasks.push(Subtask {
+                                description: desc,
+                                finished: false,
+                            }),
+                            SubtaskUpdate::Remove { idx } => {
+                                if idx == 0 || idx > task.subtasks.len() {
+                                    return Err(Error::Idx);
+                                }
+                                task.subtasks.remove(idx - 1);
+                            }
+                            SubtaskUpdate::Done { idx } => {
+                                match idx.checked_sub(1).and_then(|i| task.subtasks.get_mut(i)) {
+                                    Some(subtask) => subtask.finished = true,
+                                    None => return Err(Error::Idx),
+                                }
+                            }
+                            SubtaskUpdate::NotDone { idx } => {
+                                match idx.checked_sub(1).and_then(|i| task.subtasks.get_mut(i)) {
+                                    Some(subtask) => subtask.finished = false,
+                                    None => return Err(Error::Idx),
+                                }
+                            }
+                        }
+
+                        task.derive_finished();
+                    }
+                    UpdateSubCmd::Notes => match old_task_schedule.tasks.get_mut(&idx) {
+                        Some(task) => {
+                            let notes = editor::edit(&task.notes.join("\n"))?;
+                            task.notes = notes.trim_end().lines().map(str::to_string).collect();
+                        }
                         None => return Err(Error::Idx),
                     },
+                    UpdateSubCmd::Due { due } => {
+                        let deadline = due
+                            .as_deref()
+                            .map(|due| formats().parse_deadline(due))
+                            .transpose()?;
+                        match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => task.deadline = deadline,
+                            None => return Err(Error::Idx),
+                        }
+                    }
+                    UpdateSubCmd::Tz { tz } => {
+                        let tz = tz.as_deref().map(Zone::from_str).transpose()?;
+                        match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => {
+                                task.time.change_zone(task.tz.as_ref(), tz.as_ref())?;
+                                task.tz = tz;
+                            }
+                            None => return Err(Error::Idx),
+                        }
+                    }
+                    UpdateSubCmd::Depend(depend_update) => {
+                        let tasks = Schedule::all_tasks(db_dir).await?;
+                        let task = match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => task,
+                            None => return Err(Error::Idx),
+                        };
+
+                        match depend_update {
+                            DependUpdate::Add { id } => {
+                                if id == task.id || !tasks.iter().any(|t| t.2.id == id) {
+                                    return Err(Error::NoTask(id));
+                                }
+                                if !task.dependencies.contains(&id) {
+                                    task.dependencies.push(id);
+                                }
+                            }
+                            DependUpdate::Remove { id } => {
+                                if !task.dependencies.contains(&id) {
+                                    return Err(Error::NoTask(id));
+                                }
+                                task.dependencies.retain(|dependency| *dependency != id);
+                            }
+                        }
+                    }
+                    UpdateSubCmd::Done { force } => {
+                        let unfinished: HashSet<_> = Schedule::all_tasks(db_dir)
+                            .await?
+                            .into_iter()
+                            .filter(|(_, _, task)| !task.finished)
+                            .map(|(_, _, task)| task.id)
+                            .collect();
+                        let task = match old_task_schedule.tasks.get_mut(&idx) {
+                            Some(task) => task,
+                            None => return Err(Error::Idx),
+                        };
+
+                        let blocking: Vec<_> = task
+                            .dependencies
+                            .iter()
+                            .filter(|id| unfinished.contains(*id))
+                            .cloned()
+                            .collect();
+                        if !blocking.is_empty() {
+                            if force {
+                                eprintln!(
+                                    "warning: task is blocked by unfinished tasks {:?}",
+                                    blocking
+                                );
+                            } else {
+                                return Err(Error::Blocked(blocking));
+                            }
+                        }
+
+                        task.set_finished(true);
+                    }
                     UpdateSubCmd::NotDone => match old_task_schedule.tasks.get_mut(&idx) {
-                        Some(task) => task.finished = false,
+                        Some(task) => task.set_finished(false),
                         None => return Err(Error::Idx),
                     },
                 }
@@ -220,3 +1072,128 @@ impl SubCommand {
         Ok(())
     }
 }
+
+mod test {
+    #[test]
+    fn test_dependency_lines() {
+        use std::collections::HashMap;
+
+        use chrono::{Local, TimeZone};
+
+        use super::dependency_lines;
+        use crate::task::Task;
+
+        let date = Local.ymd(2026, 10, 19);
+        let tasks: Vec<_> = [
+            "* [ ] 9:00 {id:deploy after:review,docs} => deploy",
+            "* [ ] 10:00 {id:review after:deploy} => review",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| (date, i as u8 + 1, Task::from_str(line, &date).unwrap()))
+        .collect();
+        let tasks_by_id: HashMap<_, _> = tasks.iter().map(|t| (t.2.id.as_str(), t)).collect();
+
+        let mut lines = Vec::new();
+        dependency_lines("deploy", &tasks_by_id, "", &mut Vec::new(), &mut lines);
+        assert_eq!(
+            lines,
+            vec![
+                "├─ review 19-10-2026 (2) * [ ] 10:00 {id:review after:deploy} => review",
+                "│  └─ deploy 19-10-2026 (1) * [ ] 9:00 {id:deploy after:review,docs} => deploy",
+                "│     (cycle)",
+                "└─ docs (missing)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_date_into_gap() {
+        use std::env;
+
+        use chrono::{Local, TimeZone};
+        use clap::Clap;
+        use tokio::fs;
+
+        use super::Opts;
+        use crate::schedule::Schedule;
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-update-gap-{}", std::process::id()));
+        let date = Local.ymd(2026, 3, 28);
+        let path = Schedule::path(&db_dir, &date);
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(
+            &path,
+            "# 28-3-2026 v3\n* [ ] 2:30 {id:1 tz:Europe/Paris} => call",
+        )
+        .await
+        .unwrap();
+
+        // 2:30 does not exist in Paris on the day clocks go forward, so the task stays where it is.
+        let opts = Opts::try_parse_from(vec![
+            "timaru",
+            "update",
+            "28-3-2026",
+            "1",
+            "date",
+            "29-3-2026",
+        ])
+        .unwrap();
+        assert!(opts.subcmd.unwrap().parse(&db_dir).await.is_err());
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        assert_eq!(schedule.tasks[&1].id, "1");
+        let schedule = Schedule::read(&db_dir, Local.ymd(2026, 3, 29))
+            .await
+            .unwrap();
+        assert!(schedule.tasks.is_empty());
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_after_legacy_task() {
+        use std::env;
+
+        use chrono::{Local, TimeZone};
+        use clap::Clap;
+        use tokio::fs;
+
+        use super::Opts;
+        use crate::schedule::Schedule;
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-add-after-{}", std::process::id()));
+        let date = Local.ymd(2026, 10, 19);
+        let path = Schedule::path(&db_dir, &date);
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        // A schedule of version 1, whose task gets its id when read.
+        fs::write(&path, "# 19-10-2026\n* [ ] 9:00 => review")
+            .await
+            .unwrap();
+
+        // The id is taken from a listing, which does not write the schedule, and used by the next
+        // command, which reads it again.
+        let id = Schedule::read(&db_dir, date).await.unwrap().tasks[&1]
+            .id
+            .clone();
+        let opts = Opts::try_parse_from(vec![
+            "timaru",
+            "add",
+            "-d",
+            "19-10-2026",
+            "-t",
+            "10:00",
+            "--after",
+            &id,
+            "deploy",
+        ])
+        .unwrap();
+        opts.subcmd.unwrap().parse(&db_dir).await.unwrap();
+
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        assert_eq!(schedule.tasks[&1].id, id);
+        assert_eq!(schedule.tasks[&2].description, "deploy");
+        assert_eq!(schedule.tasks[&2].dependencies, vec![id]);
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Shell completions, whose scripts are printed by `timaru completions <shell>`.
+//!
+//! The scripts generated by clap complete the subcommands and options of timaru. Each is followed
+//! by a function running `timaru __complete` with the words typed after `timaru`, the last one
+//! being the word completed, which prints a candidate per line, as its value and a description
+//! separated by a tab: the dates which have a schedule where a date is expected, the tasks of the
+//! date given before where the index of a task is expected, and the ids of all tasks for `graph`,
+//! `add --after` and `update ... depend`. The completions of clap are used when there are none.
+//!
+//! The hook is not a subcommand of `Opts`, so that it is left out of the scripts.
+
+use std::{io::Write, path::Path};
+
+use clap::{App, ArgSettings, IntoApp};
+use clap_generate::{
+    generate,
+    generators::{Bash, Fish, Zsh},
+    Generator,
+};
+
+use crate::{cli::Opts, error::Error, format::formats, schedule::Schedule, storage};
+
+/// Name of the hidden command printing the candidates.
+pub const HOOK: &str = "__complete";
+
+const BASH_HOOK: &str = r#"
+_timaru_dynamic() {
+    local candidates
+    candidates="$(timaru __complete "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null | cut -f1)"
+    if [[ -n "$candidates" ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$candidates" -- "${COMP_WORDS[COMP_CWORD]}"))
+    else
+        _timaru "$@"
+    fi
+}
+
+complete -F _timaru_dynamic -o bashdefault -o default timaru
+"#;
+
+const ZSH_HOOK: &str = r#"
+_timaru_dynamic() {
+    local -a candidates
+    candidates=(${(f)"$(timaru __complete "${(@)words[2,CURRENT]}" 2>/dev/null)"})
+    if (( ${#candidates} )); then
+        candidates=("${(@)candidates/$'\t'/:}")
+        _describe 'timaru' candidates
+    else
+        _timaru "$@"
+    fi
+}
+
+_timaru_dynamic "$@"
+"#;
+
+const FISH_HOOK: &str = r#"
+complete -c timaru -a '(timaru __complete (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null)'
+"#;
+
+/// The completion script of `shell`, one of `bash`, `zsh` or `fish`.
+pub fn script(shell: &str) -> Result<String, Error> {
+    let mut app = Opts::into_app();
+    let mut script = Vec::new();
+    let hook = match shell {
+        "bash" => {
+            generate::<Bash, _>(&mut app, "timaru", &mut script);
+            BASH_HOOK
+        }
+        "zsh" => {
+            generate::<ZshFunctions, _>(&mut app, "timaru", &mut script);
+            ZSH_HOOK
+        }
+        "fish" => {
+            generate::<Fish, _>(&mut app, "timaru", &mut script);
+            FISH_HOOK
+        }
+        _ => return Err(Error::Parse("shell should be `bash`, `zsh` or `fish`")),
+    };
+
+    let script = String::from_utf8_lossy(&script);
+    Ok(format!("{}\n{}", script.trim_end(), hook))
+}
+
+/// The zsh script of clap, which only defines the completion functions. The one of clap ends by
+/// completing with them, which the hook does instead.
+struct ZshFunctions;
+
+impl Generator for ZshFunctions {
+    fn file_name(name: &str) -> String {
+        Zsh::file_name(name)
+    }
+
+    fn generate(app: &App, buf: &mut dyn Write) {
+        let mut script = Vec::new();
+        Zsh::generate(app, &mut script);
+        let call = format!("\n_{} \"$@\"", app.get_bin_name().unwrap_or_default());
+        let script = script.strip_suffix(call.as_bytes()).unwrap_or(&script);
+        buf.write_all(script).unwrap();
+    }
+}
+
+/// What the word being completed is expected to be.
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Date,
+    /// The index of a task of the schedule of the date.
+    Task(String),
+    Id,
+    Other,
+}
+
+/// Name of the argument of `app` whose value follows the option `word`, if it takes one.
+fn value_option(app: &App, word: &str) -> Option<String> {
+    if word.contains('=') {
+        return None;
+    }
+    let arg = match word.strip_prefix("--") {
+        Some(long) => app.get_arguments().find(|arg| arg.get_long() == Some(long)),
+        None => {
+            let short = word.strip_prefix('-')?.chars().last()?;
+            app.get_arguments()
+                .find(|arg| arg.get_short() == Some(short))
+        }
+    }?;
+
+    if arg.is_set(ArgSettings::TakesValue) {
+        Some(arg.get_name().to_string())
+    } else {
+        None
+    }
+}
+
+fn expected(words: &[String]) -> Expected {
+    let root = Opts::into_app();
+    let mut app = &root;
+    let mut path = Vec::new();
+    let mut positionals = Vec::new();
+    // The option whose value is the next word.
+    let mut option = None;
+
+    for word in words.iter().take(words.len().saturating_sub(1)) {
+        if option.take().is_some() {
+            continue;
+        }
+        if word.starts_with('-') {
+            option = value_option(app, word);
+            continue;
+        }
+        match app.find_subcommand(word) {
+            Some(subcommand) => {
+                app = subcommand;
+                path.push(word.as_str());
+                positionals.clear();
+            }
+            None => positionals.push(word.clone()),
+        }
+    }
+
+    match (option.as_deref(), path.as_slice(), positionals.as_slice()) {
+        (Some("date"), _, _) => Expected::Date,
+        (Some("after"), _, _) => Expected::Id,
+        (Some(_), _, _) => Expected::Other,
+        (None, ["list"], [])
+        | (None, ["history"], [])
+        | (None, ["remove"], [])
+        | (None, ["update"], [])
+        | (None, ["update", "date"], [])
+        | (None, ["start"], [])
+        | (None, ["restore"], [_]) => Expected::Date,
+        (None, ["remove"], [date]) | (None, ["update"], [date]) | (None, ["start"], [date]) => {
+            Expected::Task(date.clone())
+        }
+        (None, ["graph"], []) | (None, ["update", "depend", _], []) => Expected::Id,
+        _ => Expected::Other,
+    }
+}
+
+/// The candidates for the last of `words`, along with their descriptions.
+pub async fn candidates(db_dir: &Path, words: &[String]) -> Result<Vec<(String, String)>, Error> {
+    let formats = formats();
+    let mut candidates = Vec::new();
+
+    match expected(words) {
+        Expected::Date => {
+            for date in Schedule::stored_dates(db_dir).await? {
+                let weekday = date.format("%A").to_string();
+                candidates.push((formats.date(date.naive_local()), weekday));
+            }
+        }
+        Expected::Task(date) => {
+            let date = formats.parse_date(&date)?;
+            let schedule = Schedule::read(db_dir, date).await?;
+            for (idx, task) in schedule.sorted_tasks() {
+                let time = task.time.as_string_with(None, &formats);
+                candidates.push((idx.to_string(), format!("{} {}", time, task.description)));
+            }
+        }
+        Expected::Id => {
+            for (date, _, task) in Schedule::all_tasks(db_dir).await? {
+                let date = formats.date(date.naive_local());
+                candidates.push((task.id, format!("{} {}", date, task.description)));
+            }
+        }
+        Expected::Other => {}
+    }
+
+    Ok(candidates)
+}
+
+/// Prints the candidates for the last of `words`, the words typed after `timaru __complete`.
+/// Errors are left out, as they would be printed in the middle of the command line.
+pub async fn run(db_dir: &Path, words: &[String]) {
+    // Completions are asked for while typing, so the passphrase of an encrypted database is never
+    // asked for, which leaves its tasks out.
+    if storage::init_locked(db_dir).await.is_err() {
+        return;
+    }
+
+    if let Ok(candidates) = candidates(db_dir, words).await {
+        for (value, description) in candidates {
+            println!("{}\t{}", value, description);
+        }
+    }
+}
+
+mod test {
+    #[test]
+    fn test_expected() {
+        use super::{expected, Expected};
+
+        let expect = |line: &str| {
+            let mut words: Vec<_> = line.split(' ').map(str::to_string).collect();
+            if line.is_empty() {
+                words.clear();
+            }
+            expected(&words)
+        };
+
+        assert_eq!(expect(""), Expected::Other);
+        assert_eq!(expect("li"), Expected::Other);
+        assert_eq!(expect("list "), Expected::Date);
+        assert_eq!(expect("list --tz Asia/Tokyo 19"), Expected::Date);
+        assert_eq!(expect("list 19-10-2026 "), Expected::Other);
+        assert_eq!(
+            expect("remove 19-10-2026 "),
+            Expected::Task("19-10-2026".into())
+        );
+        assert_eq!(
+            expect("update 19-10-2026 "),
+            Expected::Task("19-10-2026".into())
+        );
+        assert_eq!(expect("update 19-10-2026 3 "), Expected::Other);
+        assert_eq!(expect("update 19-10-2026 3 date "), Expected::Date);
+        assert_eq!(expect("update 19-10-2026 3 depend add "), Expected::Id);
+        assert_eq!(expect("add -d "), Expected::Date);
+        assert_eq!(expect("add --after "), Expected::Id);
+        assert_eq!(expect("add -t "), Expected::Other);
+        assert_eq!(expect("graph "), Expected::Id);
+        assert_eq!(expect("restore a1b2c3 "), Expected::Date);
+    }
+
+    #[test]
+    fn test_zsh_script() {
+        use super::script;
+
+        let script = script("zsh").unwrap();
+        assert!(script.starts_with("#compdef timaru"));
+        assert!(script.ends_with("\n_timaru_dynamic \"$@\"\n"));
+        // The functions are only called by the hook.
+        assert!(!script.contains("\n_timaru \"$@\""));
+    }
+
+    #[tokio::test]
+    async fn test_candidates_read_only() {
+        use std::env;
+
+        use tokio::fs;
+
+        use super::candidates;
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-complete-{}", std::process::id()));
+        fs::create_dir_all(db_dir.join("2026/10")).await.unwrap();
+        fs::write(
+            db_dir.join("2026/10/19"),
+            "# 19-10-2026 v3\n* [ ] 9:00 {id:1a2b3c4d} => write report\n",
+        )
+        .await
+        .unwrap();
+
+        let words = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            candidates(&db_dir, &words("remove 19-10-2026 "))
+                .await
+                .unwrap(),
+            vec![("1".to_string(), "9:00 write report".to_string())]
+        );
+        assert!(candidates(&db_dir, &words("remove 20-10-2026 "))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!db_dir.join("2026/10/20").exists());
+        assert_eq!(
+            candidates(&db_dir, &words("graph ")).await.unwrap(),
+            vec![(
+                "1a2b3c4d".to_string(),
+                "19-10-2026 write report".to_string()
+            )]
+        );
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
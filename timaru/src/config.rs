@@ -0,0 +1,75 @@
+use std::{io, path::PathBuf};
+
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::{error::Error, setup::config_dir};
+
+/// Options read from `config.toml` in the config directory. Every option is optional, so a
+/// missing file or section means the defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub http: HttpConfig,
+    pub sync: SyncConfig,
+    pub history: HistoryConfig,
+    pub encryption: EncryptionConfig,
+    pub format: FormatConfig,
+}
+
+/// Options of `timaru serve http`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Token clients have to send as `Authorization: Bearer <token>`. No token is required if
+    /// not set.
+    pub token: Option<String>,
+}
+
+/// Options of `timaru sync`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Remote used when none is given on the command line.
+    pub remote: Option<String>,
+}
+
+/// Options of the git history of the database.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Commit the changes to schedules in a git repository in the database directory.
+    pub git: bool,
+}
+
+/// Where the passphrase of an encrypted database comes from. It is asked for if neither is set,
+/// and `$TIMARU_PASSPHRASE` is not set either.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// File whose content is the passphrase.
+    pub keyfile: Option<PathBuf>,
+    /// Shell command printing the passphrase, e.g. to get it from a password manager.
+    pub passphrase_command: Option<String>,
+}
+
+/// Formats dates and times are shown and read in, as strftime patterns. See `format`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    /// Format of dates, `%-d-%-m-%Y` if not set.
+    pub date: Option<String>,
+    /// Format of times, `%-H:%M` if not set.
+    pub time: Option<String>,
+}
+
+impl Config {
+    pub async fn load() -> Result<Config, Error> {
+        let path = config_dir().await?.join("config.toml");
+        match fs::read_to_string(&path).await {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::{env, fs, process::Command};
+
+use crate::error::Error;
+
+/// Opens `$EDITOR` (or `vi` if it is not set) on a temporary file containing `content`, and
+/// returns the content of the file once the editor exits. `$EDITOR` is run by the shell, so it can
+/// contain arguments like `code -w`.
+pub fn edit(content: &str) -> Result<String, Error> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let file = env::temp_dir().join(format!("timaru-{}.md", std::process::id()));
+    fs::write(&file, content)?;
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&file)
+        .status();
+    let edited = fs::read_to_string(&file);
+    let _ = fs::remove_file(&file);
+
+    match status {
+        Ok(status) if status.success() => Ok(edited?),
+        _ => Err(Error::Editor(editor)),
+    }
+}
@@ -10,12 +10,50 @@ pub enum Error {
     EnvVar,
     #[error("error: parsing error : {0}")]
     Parse(&'static str),
+    #[error("error: data of format version {0} was written by a newer version of timaru")]
+    NewerVersion(u32),
     #[error("error: invalid index")]
     Idx,
+    #[error("error: no task is being worked on")]
+    NotTracking,
+    #[error("error: no task with id {0}")]
+    NoTask(String),
+    #[error(
+        "error: task is blocked by unfinished tasks {0:?}, use --force to mark it done anyway"
+    )]
+    Blocked(Vec<String>),
+    #[error("error: editor {0} exited unsuccessfully")]
+    Editor(String),
     #[error("error: IO error : {0:?}")]
     IO(#[from] io::Error),
     #[error("error: TUI error : {0:?}")]
     TUI(#[from] crossterm::ErrorKind),
+    #[error("error: invalid config file: {0}")]
+    Config(#[from] toml::de::Error),
+    #[error("error: HTTP error: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("error: invalid format `{0}` in the [format] section of the config file")]
+    Format(String),
+    #[error("error: no remote given and no remote set in the [sync] section of the config file")]
+    NoRemote,
+    #[error("error: unable to decrypt {0}, wrong passphrase or key")]
+    Decrypt(PathBuf),
+    #[error("error: {0}")]
+    Encryption(&'static str),
+    #[error("error: git failed: {0}")]
+    Git(String),
+    #[error("error: no history, set `git = true` in the [history] section of the config file")]
+    NoHistory,
+    #[error("error: invalid backup: {0}")]
+    Backup(String),
+    #[error("error: WebDAV request failed: {0}")]
+    WebDav(String),
+    #[error("error: invalid sync state {0}: {1}, remove it to sync as if for the first time")]
+    SyncState(PathBuf, String),
+    #[error("error: shell error: {0}")]
+    Shell(String),
+    #[error("error: JSON error: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("error: Log error: {0:?}")]
     Log(#[from] log::SetLoggerError),
 }
@@ -0,0 +1,405 @@
+//! CSV export and import of schedules.
+//!
+//! Each task is one row with the columns `date`, `id`, `start`, `end`, `kind`, `description`,
+//! `pomodoro_total`, `pomodoro_done`, `finished` and `tz`, preceded by a header row. `kind` is one
+//! of `precise`, `general`, `period` or `general-period`, and `end` is empty unless the task time
+//! is a period. Pomodoro columns are empty if the task has no pomodoro. Times are on the clocks of
+//! the timezone in `tz`, or the local ones if it is empty, and rows without the `tz` column are
+//! read as such. Dates and times are written in the formats of the `[format]` config section, and
+//! read in them or in the canonical ones.
+
+use std::{path::Path, str::FromStr};
+
+use chrono::{Date, DateTime, Local, Timelike};
+
+use super::import_tasks;
+use crate::{
+    error::Error,
+    format::{formats, Formats, TIME},
+    task::{Task, TaskTime, Zone},
+};
+
+pub const CSV_HEADER: &str =
+    "date,id,start,end,kind,description,pomodoro_total,pomodoro_done,finished,tz";
+
+/// A task read from a CSV row, along with the date of its schedule.
+#[derive(Debug, Clone)]
+pub struct CsvRow {
+    pub date: Date<Local>,
+    /// Id of the task, empty if the row is for a new task.
+    pub id: String,
+    pub time: TaskTime,
+    pub description: String,
+    pub pomodoro: Option<(u8, u8)>,
+    pub finished: bool,
+    pub tz: Option<Zone>,
+}
+
+/// Quotes `field` if it contains a comma, a quote or a line break.
+fn quote_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits CSV text into records of fields, handling quoted fields with line breaks in them.
+fn parse_records(input: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (in_quotes, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => in_quotes = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => in_quotes = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(Error::Parse("unterminated quoted CSV field"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+impl TaskTime {
+    /// The kind, start and end of the time, as stored in the CSV columns.
+    pub fn as_csv_fields(&self) -> (&'static str, String, String) {
+        self.as_csv_fields_with(None, &Formats::default())
+    }
+
+    /// Same as `as_csv_fields`, with the times on the clocks of `zone`, or the local ones if none
+    /// is given, and in the time format of `formats`. Times with seconds are written in the
+    /// canonical format followed by the seconds, like deadlines.
+    pub fn as_csv_fields_with(
+        &self,
+        zone: Option<&Zone>,
+        formats: &Formats,
+    ) -> (&'static str, String, String) {
+        let hm = |time: &DateTime<Local>| {
+            let time = Zone::wall_clock_of(zone, time).time();
+            if time.second() != 0 {
+                time.format(&format!("{}:%S", TIME)).to_string()
+            } else {
+                formats.time(time)
+            }
+        };
+
+        match self {
+            TaskTime::Precise { time } => ("precise", hm(time), String::new()),
+            TaskTime::General { time } => ("general", time.as_str().to_string(), String::new()),
+            TaskTime::Period { start, end } => ("period", hm(start), hm(end)),
+            TaskTime::GeneralPeriod { start, end } => (
+                "general-period",
+                start.as_str().to_string(),
+                end.as_str().to_string(),
+            ),
+        }
+    }
+}
+
+impl Task {
+    pub fn as_csv_row(&self, date: &Date<Local>) -> String {
+        let formats = formats();
+        let (kind, start, end) = self.time.as_csv_fields_with(self.tz.as_ref(), &formats);
+        let (pomodoro_total, pomodoro_done) = match self.pomodoro {
+            Some((total, done)) => (total.to_string(), done.to_string()),
+            None => (String::new(), String::new()),
+        };
+
+        [
+            formats.date(date.naive_local()),
+            self.id.clone(),
+            start,
+            end,
+            kind.to_string(),
+            self.description.clone(),
+            pomodoro_total,
+            pomodoro_done,
+            self.finished.to_string(),
+            self.tz.map(|tz| tz.as_string()).unwrap_or_default(),
+        ]
+        .iter()
+        .map(|field| quote_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Renders the tasks, along with the dates of their schedules, as CSV with a header row.
+pub fn tasks_as_csv<'a>(tasks: impl Iterator<Item = (&'a Date<Local>, &'a Task)>) -> String {
+    let mut s = format!("{}\n", CSV_HEADER);
+    for (date, task) in tasks {
+        s += &format!("{}\n", task.as_csv_row(date));
+    }
+    s
+}
+
+/// Reads the rows of CSV produced by `tasks_as_csv`, skipping the header row if present. Rows
+/// written before the `tz` column was added are read too.
+pub fn rows_from_csv(input: &str) -> Result<Vec<CsvRow>, Error> {
+    let header: Vec<_> = CSV_HEADER.split(',').collect();
+    let mut rows = Vec::new();
+
+    for record in parse_records(input)? {
+        if record == header || record == header[..9] || record.iter().all(|field| field.is_empty())
+        {
+            continue;
+        }
+        if record.len() != 9 && record.len() != 10 {
+            return Err(Error::Parse("CSV row does not have 10 columns"));
+        }
+
+        let formats = formats();
+        let date = formats.parse_date(&record[0])?;
+        let tz = match record.get(9).map(String::as_str) {
+            None | Some("") => None,
+            Some(tz) => Some(Zone::from_str(tz)?),
+        };
+        let time = match (record[4].as_str(), record[3].is_empty()) {
+            ("precise", true) | ("general", true) => {
+                TaskTime::from_str_in(&formats.canonical_time(&record[2]), &date, tz.as_ref())?
+            }
+            ("period", false) | ("general-period", false) => {
+                let period = format!("{} - {}", record[2], record[3]);
+                TaskTime::from_str_in(&formats.canonical_time(&period), &date, tz.as_ref())?
+            }
+            _ => return Err(Error::Parse("CSV time kind")),
+        };
+        let pomodoro = match (record[6].as_str(), record[7].as_str()) {
+            ("", "") => None,
+            (total, done) => match (total.parse(), done.parse()) {
+                (Ok(total), Ok(done)) => Some((total, done)),
+                _ => return Err(Error::Parse("CSV pomodoro")),
+            },
+        };
+        let finished = match record[8].to_lowercase().as_str() {
+            "true" | "x" | "1" => true,
+            "false" | "" | "0" => false,
+            _ => return Err(Error::Parse("CSV finished")),
+        };
+
+        rows.push(CsvRow {
+            date,
+            id: record[1].clone(),
+            time,
+            description: record[5].clone(),
+            pomodoro,
+            finished,
+            tz,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Creates or updates the tasks of `rows`, printing what is done for each row. Rows with the id
+/// of an existing task update it, moving it to the date of the row if needed, and other rows
+/// create new tasks. Nothing is changed if `dry_run` is set.
+pub async fn import_csv_rows(db_dir: &Path, rows: Vec<CsvRow>, dry_run: bool) -> Result<(), Error> {
+    let tasks = rows
+        .into_iter()
+        .map(|row| {
+            let task = Task {
+                id: row.id,
+                pomodoro: row.pomodoro,
+                finished: row.finished,
+                tz: row.tz,
+                ..Task::new(row.time, row.description)
+            };
+            (row.date, task)
+        })
+        .collect();
+
+    import_tasks(db_dir, tasks, dry_run, |old, new| Task {
+        time: new.time,
+        description: new.description,
+        pomodoro: new.pomodoro,
+        finished: new.finished,
+        tz: new.tz,
+        ..old
+    })
+    .await
+}
+
+mod test {
+    #[test]
+    fn test_csv_quoting() {
+        use super::{parse_records, quote_field};
+
+        let field = "review, then \"deploy\"\nnext line";
+        let record = format!("1-1-2021,{},plain", quote_field(field));
+        assert_eq!(
+            parse_records(&record).unwrap(),
+            vec![vec!["1-1-2021", field, "plain"]]
+        );
+    }
+
+    #[test]
+    fn test_csv_tz() {
+        use std::str::FromStr;
+
+        use chrono::NaiveDate;
+
+        use super::{rows_from_csv, tasks_as_csv, CSV_HEADER};
+        use crate::task::{Task, TaskTime, Zone};
+
+        let input = format!(
+            "{}\n19-10-2026,1a2b3c4d,22:00,23:30,period,call,,,false,America/New_York\n",
+            CSV_HEADER
+        );
+        let rows = rows_from_csv(&input).unwrap();
+        let new_york = Zone::from_str("America/New_York").unwrap();
+        assert_eq!(rows[0].tz, Some(new_york));
+        match &rows[0].time {
+            TaskTime::Period { start, .. } => assert_eq!(
+                new_york.wall_clock(start),
+                NaiveDate::from_ymd(2026, 10, 19).and_hms(22, 0, 0)
+            ),
+            _ => panic!("not a period"),
+        }
+
+        let task = Task {
+            id: rows[0].id.clone(),
+            tz: rows[0].tz,
+            ..Task::new(rows[0].time.clone(), rows[0].description.clone())
+        };
+        assert_eq!(tasks_as_csv(std::iter::once((&rows[0].date, &task))), input);
+
+        // Rows exported before the `tz` column are on the local clock.
+        let rows = rows_from_csv(
+            "date,id,start,end,kind,description,pomodoro_total,pomodoro_done,finished\n\
+             19-10-2026,1a2b3c4d,9:00,,precise,call,,,false\n",
+        )
+        .unwrap();
+        assert_eq!(rows[0].tz, None);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        use chrono::{Local, TimeZone};
+
+        use super::{rows_from_csv, tasks_as_csv};
+        use crate::task::{Task, TaskTime, TimeOfDay};
+
+        let date = Local.ymd(2026, 10, 19);
+        let times = vec![
+            TaskTime::Precise {
+                time: date.and_hms(9, 0, 30),
+            },
+            TaskTime::Period {
+                start: date.and_hms(10, 0, 0),
+                end: date.and_hms(11, 30, 15),
+            },
+            TaskTime::General {
+                time: TimeOfDay::Evening,
+            },
+            TaskTime::GeneralPeriod {
+                start: TimeOfDay::Custom("after lunch".to_string()),
+                end: TimeOfDay::Night,
+            },
+        ];
+        let tasks: Vec<_> = times
+            .into_iter()
+            .enumerate()
+            .map(|(i, time)| Task {
+                pomodoro: if i % 2 == 0 { Some((4, i as u8)) } else { None },
+                finished: i % 2 == 1,
+                ..Task::new(time, format!("task, \"{}\"", i))
+            })
+            .collect();
+
+        let csv = tasks_as_csv(tasks.iter().map(|task| (&date, task)));
+        assert!(csv.contains(",9:00:30,,precise,"));
+        assert!(csv.contains(",10:00,11:30:15,period,"));
+        let rows = rows_from_csv(&csv).unwrap();
+        assert_eq!(rows.len(), tasks.len());
+        for (row, task) in rows.iter().zip(tasks.iter()) {
+            assert_eq!(row.date, date);
+            assert_eq!(row.id, task.id);
+            assert_eq!(row.time, task.time);
+            assert_eq!(row.description, task.description);
+            assert_eq!(row.pomodoro, task.pomodoro);
+            assert_eq!(row.finished, task.finished);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_rows() {
+        use std::env;
+
+        use chrono::{Local, TimeZone};
+        use tokio::fs;
+
+        use super::{import_csv_rows, CsvRow};
+        use crate::{schedule::Schedule, task::TaskTime};
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-import-csv-{}", std::process::id()));
+        let date = Local.ymd(2026, 10, 19);
+        let next_date = Local.ymd(2026, 11, 1);
+        let path = Schedule::path(&db_dir, &date);
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(
+            path,
+            "# 19-10-2026 v3\n* [ ] 9:00 {id:1a2b3c4d} => write report",
+        )
+        .await
+        .unwrap();
+        // The schedules of November can not be written.
+        fs::write(db_dir.join("2026/11"), "").await.unwrap();
+        let row = |date: chrono::Date<Local>, id: &str| CsvRow {
+            date,
+            id: id.to_string(),
+            time: TaskTime::Precise {
+                time: date.and_hms(9, 0, 0),
+            },
+            description: "write report".to_string(),
+            pomodoro: None,
+            finished: true,
+            tz: None,
+        };
+
+        // The task is kept where it is if it can not be moved.
+        assert!(
+            import_csv_rows(&db_dir, vec![row(next_date, "1a2b3c4d")], false)
+                .await
+                .is_err()
+        );
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        assert_eq!(schedule.tasks[&1].id, "1a2b3c4d");
+        assert!(!schedule.tasks[&1].finished);
+
+        // Tasks finished by the import are finished today, as CSV has no completion date.
+        import_csv_rows(&db_dir, vec![row(date, "1a2b3c4d"), row(date, "")], false)
+            .await
+            .unwrap();
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        assert_eq!(schedule.tasks.len(), 2);
+        assert!(schedule
+            .tasks
+            .values()
+            .all(|task| task.finished && task.done == Some(Local::today())));
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
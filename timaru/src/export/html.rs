@@ -0,0 +1,287 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{Date, Datelike, Duration, Local};
+
+use crate::{
+    error::Error,
+    format::formats,
+    heatmap::MONTHS,
+    schedule::Schedule,
+    task::{Priority, Task, TimeOfDay},
+};
+
+/// Inlined so that the page has no external assets.
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 1em; color: #24292e; }
+table { border-collapse: collapse; width: 100%; table-layout: fixed; }
+th { padding: 4px; background: #f6f8fa; border: 1px solid #d0d7de; }
+td { vertical-align: top; height: 8em; padding: 4px; border: 1px solid #d0d7de; }
+td.outside { background: #f6f8fa; }
+td.today { background: #fff8c5; }
+h2 { margin: 1em 0 0.5em; }
+.day { font-weight: bold; margin-bottom: 4px; }
+.task { margin: 2px 0; padding: 2px 4px; border-left: 4px solid #afb8c1; font-size: 0.85em; }
+.task.done .description { text-decoration: line-through; color: #6e7781; }
+.time { color: #57606a; margin-right: 4px; }
+.priority-a { border-left-color: #cf222e; }
+.priority-b { border-left-color: #bf8700; }
+.priority-c { border-left-color: #0969da; }
+.morning, .noon { background: #fff8e1; }
+.afternoon { background: #e8f5e9; }
+.evening, .night, .midnight { background: #ede7f6; }
+.pomodoro { height: 4px; margin-top: 2px; background: #eaeef2; }
+.pomodoro div { height: 100%; background: #cf222e; }
+.due { color: #cf222e; font-size: 0.9em; }
+";
+
+/// How the days of the HTML calendar are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlView {
+    /// A single grid of the weeks of the range.
+    Week,
+    /// A grid of weeks for each month of the range, under the name of the month.
+    Month,
+}
+
+impl FromStr for HtmlView {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        match input {
+            "week" => Ok(HtmlView::Week),
+            "month" => Ok(HtmlView::Month),
+            _ => Err(Error::Parse("view, expected `week` or `month`")),
+        }
+    }
+}
+
+/// Escapes `input` for use in HTML text and attribute values.
+fn escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Task {
+    /// Renders the task as an HTML block for a day of the calendar, its background showing the
+    /// time of day it starts at.
+    pub fn as_html(&self) -> String {
+        let mut classes = vec!["task"];
+        if self.finished {
+            classes.push("done");
+        }
+        classes.push(match self.priority {
+            Some(Priority::High) => "priority-a",
+            Some(Priority::Medium) => "priority-b",
+            Some(Priority::Low) | Some(Priority::Lower(_)) => "priority-c",
+            None => "",
+        });
+        classes.push(
+            match self.time.start_time().map(|time| TimeOfDay::of(&time)) {
+                Some(TimeOfDay::Morning) => "morning",
+                Some(TimeOfDay::Noon) => "noon",
+                Some(TimeOfDay::AfterNoon) => "afternoon",
+                Some(TimeOfDay::Evening) => "evening",
+                Some(TimeOfDay::Night) => "night",
+                Some(TimeOfDay::MidNight) => "midnight",
+                Some(TimeOfDay::Custom(_)) | None => "",
+            },
+        );
+        classes.retain(|class| !class.is_empty());
+
+        let mut s = format!(
+            "<div class=\"{}\" title=\"{}\"><span class=\"time\">{}</span>\
+             <span class=\"description\">{}</span>",
+            classes.join(" "),
+            escape(&self.id),
+            escape(&self.time.as_string_with(None, &formats())),
+            escape(&self.description)
+        );
+        if let Some(deadline) = &self.deadline {
+            s += &format!(
+                " <span class=\"due\">due {}</span>",
+                escape(&deadline.as_string_with(&formats()))
+            );
+        }
+        if let Some((total, done)) = self.pomodoro {
+            let percent = if total == 0 {
+                0
+            } else {
+                done.min(total) as u32 * 100 / total as u32
+            };
+            s += &format!(
+                "<div class=\"pomodoro\" title=\"pomodoro {}/{}\">\
+                 <div style=\"width: {}%\"></div></div>",
+                done, total, percent
+            );
+        }
+        s += "</div>\n";
+
+        s
+    }
+}
+
+/// Renders the weeks from `from` to `to` as a table, days outside of them being empty cells.
+fn weeks_as_html(
+    by_date: &HashMap<Date<Local>, &Schedule>,
+    from: Date<Local>,
+    to: Date<Local>,
+) -> String {
+    let today = Local::today();
+    let mut s = String::from("<table>\n<tr>");
+    for weekday in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter() {
+        s += &format!("<th>{}</th>", weekday);
+    }
+    s += "</tr>\n";
+
+    // Weeks start on Monday.
+    let mut date = from - Duration::days(from.weekday().num_days_from_monday() as i64);
+    while date <= to {
+        s += "<tr>\n";
+        for _ in 0..7 {
+            let outside = date < from || date > to;
+            let class = if outside {
+                " class=\"outside\""
+            } else if date == today {
+                " class=\"today\""
+            } else {
+                ""
+            };
+            s += &format!(
+                "<td{}><div class=\"day\">{} {}</div>\n",
+                class,
+                date.day(),
+                MONTHS[date.month0() as usize]
+            );
+            // Days of other months are left empty, as their own month shows them.
+            match by_date.get(&date) {
+                Some(schedule) if !outside => {
+                    for (_, task) in schedule.sorted_tasks() {
+                        s += &task.as_html();
+                    }
+                }
+                _ => {}
+            }
+            s += "</td>\n";
+            date = date + Duration::days(1);
+        }
+        s += "</tr>\n";
+    }
+
+    s += "</table>\n";
+    s
+}
+
+/// Renders the schedules as a self-contained HTML page with a calendar grid of the days from
+/// `first` to `last`, each day listing its tasks in the order of their `TaskTime`. The dates
+/// default to the first and last dates of the schedules. With `HtmlView::Month`, each month has
+/// its own grid under its name.
+pub fn schedules_as_html(
+    schedules: &[Schedule],
+    first: Option<Date<Local>>,
+    last: Option<Date<Local>>,
+    view: HtmlView,
+) -> String {
+    let today = Local::today();
+    let by_date: HashMap<Date<Local>, &Schedule> = schedules
+        .iter()
+        .map(|schedule| (schedule.date, schedule))
+        .collect();
+
+    let first = first
+        .or_else(|| by_date.keys().min().copied())
+        .unwrap_or(today);
+    let last = last
+        .or_else(|| by_date.keys().max().copied())
+        .unwrap_or(today);
+    let title = format!(
+        "timaru: {} to {}",
+        formats().date(first.naive_local()),
+        formats().date(last.naive_local())
+    );
+
+    let mut s = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title
+    );
+
+    match view {
+        HtmlView::Week => s += &weeks_as_html(&by_date, first, last),
+        HtmlView::Month => {
+            let mut month = first.with_day(1).unwrap_or(first);
+            while month <= last {
+                let next = (month + Duration::days(31)).with_day(1).unwrap_or(last);
+                s += &format!(
+                    "<h2>{} {}</h2>\n",
+                    MONTHS[month.month0() as usize],
+                    month.year()
+                );
+                s += &weeks_as_html(
+                    &by_date,
+                    month.max(first),
+                    (next - Duration::days(1)).min(last),
+                );
+                month = next;
+            }
+        }
+    }
+
+    s += "</body>\n</html>\n";
+    s
+}
+
+mod test {
+    #[test]
+    fn test_html() {
+        use std::{env, str::FromStr};
+
+        use chrono::{Local, TimeZone};
+
+        use super::{schedules_as_html, HtmlView};
+        use crate::schedule::Schedule;
+
+        let schedule = |name: &str, input: &str| {
+            let file =
+                env::temp_dir().join(format!("timaru-test-html-{}-{}", name, std::process::id()));
+            let mut schedule = Schedule::from_str(file, input).unwrap();
+            schedule.read_only = true;
+            schedule
+        };
+        let schedules = [
+            schedule(
+                "oct",
+                "# 30-10-2026 v3
+* [X] 9:00 (4, 2) {id:1 priority:A} => review <draft>
+* [ ] evening {id:2} => read",
+            ),
+            schedule("nov", "# 2-11-2026 v3\n* [ ] 10:00 {id:3} => plan"),
+        ];
+        let first = Some(Local.ymd(2026, 10, 28));
+        let last = Some(Local.ymd(2026, 11, 3));
+
+        let html = schedules_as_html(&schedules, first, last, HtmlView::Week);
+        assert!(!html.contains("href") && !html.contains("src="));
+        assert_eq!(html.matches("<table>").count(), 1);
+        assert!(html.contains(
+            "<div class=\"task done priority-a morning\" title=\"1\"><span class=\"time\">9:00</span>\
+             <span class=\"description\">review &lt;draft&gt;</span>\
+             <div class=\"pomodoro\" title=\"pomodoro 2/4\"><div style=\"width: 50%\"></div></div></div>"
+        ));
+        assert!(html.find("review").unwrap() < html.find("read").unwrap());
+        assert!(html.find("read").unwrap() < html.find("plan").unwrap());
+
+        // Each month has its own grid, where the days of the other month are left empty.
+        let html = schedules_as_html(&schedules, first, last, HtmlView::Month);
+        assert_eq!(html.matches("<table>").count(), 2);
+        let november = html.find("<h2>Nov 2026</h2>").unwrap();
+        assert!(html.find("<h2>Oct 2026</h2>").unwrap() < november);
+        assert_eq!(html.matches("review").count(), 1);
+        assert!(html.find("review").unwrap() < november);
+        assert!(html.find("plan").unwrap() > november);
+
+        assert!(HtmlView::from_str("year").is_err());
+    }
+}
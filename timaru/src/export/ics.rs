@@ -0,0 +1,380 @@
+//! iCalendar (RFC 5545) conversion of tasks, used by the CalDAV server.
+//!
+//! Tasks are written as `VTODO` components whose `UID` is the task id. `DTSTART` holds the start
+//! of the task, as a date for times of day like `morning`, and `X-TIMARU-TIME` holds the task
+//! time as written in schedule files so that it can be read back exactly, on the clocks of the
+//! timezone of the task kept in `X-TIMARU-TZ` if it has one. Pomodoro cycles and dependencies are
+//! kept in `X-TIMARU-POMODORO` and `X-TIMARU-AFTER`, notes in `DESCRIPTION`, and the date on
+//! which the task was finished in `COMPLETED`, at noon as only the date is known. Both `VTODO`
+//! and `VEVENT` components can be read, times with a `TZID` being read on the clocks of that
+//! timezone, which has to be an IANA timezone like `Europe/Paris`.
+
+use std::str::FromStr;
+
+use chrono::{Date, DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{
+    change_parse_err,
+    error::Error,
+    task::{Deadline, Priority, Task, TaskTime, Zone},
+};
+
+/// A `DATE` or `DATE-TIME` value.
+enum IcalTime {
+    Date(Date<Local>),
+    DateTime(DateTime<Local>),
+}
+
+impl IcalTime {
+    fn date(&self) -> Date<Local> {
+        match self {
+            IcalTime::Date(date) => *date,
+            IcalTime::DateTime(time) => time.date(),
+        }
+    }
+}
+
+fn ical_date(date: &Date<Local>) -> String {
+    format!("{}{:02}{:02}", date.year(), date.month(), date.day())
+}
+
+fn ical_datetime(time: &DateTime<Local>) -> String {
+    time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// Parses a `DATE` or `DATE-TIME` value, floating date-times being on the clocks of `zone` if
+/// given, or else local.
+fn parse_ical_time(input: &str, zone: Option<&Zone>) -> Result<IcalTime, Error> {
+    if input.len() == 8 {
+        let date = change_parse_err!(NaiveDate::parse_from_str(input, "%Y%m%d"), "iCal date");
+        if let Some(date) = Local.from_local_date(&date).single() {
+            return Ok(IcalTime::Date(date));
+        }
+    } else if input.ends_with('Z') {
+        let time = change_parse_err!(
+            Utc.datetime_from_str(input, "%Y%m%dT%H%M%SZ"),
+            "iCal date-time"
+        );
+        return Ok(IcalTime::DateTime(time.with_timezone(&Local)));
+    } else {
+        let time = change_parse_err!(
+            NaiveDateTime::parse_from_str(input, "%Y%m%dT%H%M%S"),
+            "iCal date-time"
+        );
+        return Zone::at_wall_clock_of(zone, &time).map(IcalTime::DateTime);
+    }
+
+    Err(Error::Parse("iCal time"))
+}
+
+fn escape_text(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(input: &str) -> String {
+    let mut s = String::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => s.push('\n'),
+            Some(c) => s.push(c),
+            None => {}
+        }
+    }
+    s
+}
+
+/// Folds a content line into lines of at most 75 bytes, each ending with CRLF.
+fn fold_line(line: &str) -> String {
+    let mut s = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            s += "\r\n ";
+            width = 1;
+        }
+        s.push(c);
+        width += c.len_utf8();
+    }
+    s += "\r\n";
+    s
+}
+
+impl Task {
+    /// Renders the task, planned on `date`, as a `VTODO` component.
+    pub fn as_vtodo(&self, date: &Date<Local>) -> String {
+        let mut lines = vec![
+            "BEGIN:VTODO".to_string(),
+            format!("UID:{}", self.id),
+            format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+            format!("SUMMARY:{}", escape_text(&self.description)),
+        ];
+
+        match &self.time {
+            TaskTime::Precise { time } | TaskTime::Period { start: time, .. } => {
+                lines.push(format!("DTSTART:{}", ical_datetime(time)))
+            }
+            TaskTime::General { .. } | TaskTime::GeneralPeriod { .. } => {
+                lines.push(format!("DTSTART;VALUE=DATE:{}", ical_date(date)))
+            }
+        }
+        lines.push(format!(
+            "X-TIMARU-TIME:{}",
+            escape_text(&self.time.as_string_in(self.tz.as_ref()))
+        ));
+        if let Some(tz) = &self.tz {
+            lines.push(format!("X-TIMARU-TZ:{}", tz.as_string()));
+        }
+
+        if let Some(deadline) = &self.deadline {
+            match deadline.time {
+                Some(_) => lines.push(format!("DUE:{}", ical_datetime(&deadline.as_datetime()))),
+                None => lines.push(format!("DUE;VALUE=DATE:{}", ical_date(&deadline.date))),
+            }
+        }
+        if let Some(priority) = self.priority {
+            lines.push(format!(
+                "PRIORITY:{}",
+                match priority {
+                    Priority::High => 1,
+                    Priority::Medium => 5,
+                    Priority::Low | Priority::Lower(_) => 9,
+                }
+            ));
+        }
+        lines.push(format!(
+            "STATUS:{}",
+            if self.finished {
+                "COMPLETED"
+            } else {
+                "NEEDS-ACTION"
+            }
+        ));
+        if let Some(done) = &self.done {
+            lines.push(format!(
+                "COMPLETED:{}",
+                done.and_hms(12, 0, 0)
+                    .with_timezone(&Utc)
+                    .format("%Y%m%dT%H%M%SZ")
+            ));
+        }
+        if let Some((total, done)) = self.pomodoro {
+            lines.push(format!("X-TIMARU-POMODORO:{}/{}", done, total));
+        }
+        if !self.dependencies.is_empty() {
+            lines.push(format!("X-TIMARU-AFTER:{}", self.dependencies.join(",")));
+        }
+        if !self.notes.is_empty() {
+            lines.push(format!(
+                "DESCRIPTION:{}",
+                escape_text(&self.notes.join("\n"))
+            ));
+        }
+        lines.push("END:VTODO".to_string());
+
+        lines.iter().map(|line| fold_line(line)).collect()
+    }
+
+    /// Renders the task, planned on `date`, as a `VCALENDAR` object holding a single `VTODO`.
+    pub fn as_ical(&self, date: &Date<Local>) -> String {
+        fold_line("BEGIN:VCALENDAR")
+            + &fold_line("VERSION:2.0")
+            + &fold_line("PRODID:-//timaru//timaru//EN")
+            + &self.as_vtodo(date)
+            + &fold_line("END:VCALENDAR")
+    }
+
+    /// Parses the first `VTODO` or `VEVENT` component of a `VCALENDAR` object into a task and
+    /// the date of its schedule, which is the date of `DTSTART`, or today if it has none. The
+    /// task has the `UID` of the component as id, which is empty if it has none.
+    pub fn from_ical(input: &str) -> Result<(Date<Local>, Task), Error> {
+        // Unfold lines continued on the next line with a leading space or tab.
+        let mut lines: Vec<String> = Vec::new();
+        for line in input.lines() {
+            match (
+                line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+                lines.last_mut(),
+            ) {
+                (Some(rest), Some(last)) => *last += rest,
+                _ => lines.push(line.to_string()),
+            }
+        }
+
+        // Properties of components nested in the task, like `VALARM`, are skipped.
+        let mut component = None;
+        let mut nested = 0;
+        let mut properties = Vec::new();
+        for line in lines.iter() {
+            match (component, line.as_str()) {
+                (None, "BEGIN:VTODO") | (None, "BEGIN:VEVENT") => component = Some(&line[6..]),
+                (Some(name), end) if nested == 0 && end == format!("END:{}", name) => break,
+                (Some(_), line) if line.starts_with("BEGIN:") => nested += 1,
+                (Some(_), line) if line.starts_with("END:") => nested -= 1,
+                (Some(_), _) if nested > 0 => {}
+                (Some(_), line) => {
+                    let (name, value) = match line.find(':') {
+                        Some(i) => (&line[..i], &line[i + 1..]),
+                        None => continue,
+                    };
+                    let mut params = name.split(';');
+                    let name = params.next().unwrap_or_default();
+                    let tzid = params
+                        .filter_map(|param| param.split_once('='))
+                        .find(|(param, _)| param.eq_ignore_ascii_case("TZID"))
+                        .map(|(_, tzid)| tzid.trim_matches('"'));
+                    properties.push((name.to_uppercase(), tzid, value));
+                }
+                (None, _) => {}
+            }
+        }
+        if component.is_none() {
+            return Err(Error::Parse("no VTODO or VEVENT in iCal object"));
+        }
+
+        let get = |name: &str| {
+            properties
+                .iter()
+                .find(|(property, _, _)| property == name)
+                .map(|(_, _, value)| *value)
+        };
+        let get_time =
+            |name: &str| match properties.iter().find(|(property, _, _)| property == name) {
+                Some((_, Some(tzid), value)) => {
+                    parse_ical_time(value, Some(&Zone::from_str(tzid)?)).map(Some)
+                }
+                Some((_, None, value)) => parse_ical_time(value, None).map(Some),
+                None => Ok(None),
+            };
+
+        let tz = get("X-TIMARU-TZ").map(Zone::from_str).transpose()?;
+        let start = get_time("DTSTART")?;
+        let end = get_time("DTEND")?;
+        // Tasks with a timezone are on the schedule of their date on its clocks.
+        let date = match (&start, &tz) {
+            (Some(IcalTime::DateTime(start)), Some(_)) => {
+                let date = Zone::wall_clock_of(tz.as_ref(), start).date();
+                Local
+                    .from_local_date(&date)
+                    .earliest()
+                    .ok_or(Error::Parse("date can not exist"))?
+            }
+            (start, _) => start.as_ref().map_or_else(Local::today, IcalTime::date),
+        };
+
+        // `X-TIMARU-TIME` is only used if its start was not changed by another client.
+        let timaru_time = get("X-TIMARU-TIME")
+            .map(|time| TaskTime::from_str_in(&unescape_text(time), &date, tz.as_ref()))
+            .transpose()?;
+        let time = match (timaru_time, start, end) {
+            (Some(time), Some(IcalTime::Date(_)), _) | (Some(time), None, _) => time,
+            (Some(time), Some(IcalTime::DateTime(start)), _)
+                if time.start_time() == Some(start.time()) =>
+            {
+                time
+            }
+            (_, Some(IcalTime::DateTime(start)), Some(IcalTime::DateTime(end))) => {
+                TaskTime::Period { start, end }
+            }
+            (_, Some(IcalTime::DateTime(time)), _) => TaskTime::Precise { time },
+            (None, Some(IcalTime::Date(_)), _) | (None, None, _) => TaskTime::Precise {
+                time: Zone::at_wall_clock_of(
+                    None,
+                    &date.naive_local().and_time(Local::now().time()),
+                )?,
+            },
+        };
+
+        let deadline = match get_time("DUE")? {
+            Some(IcalTime::Date(date)) => Some(Deadline { date, time: None }),
+            Some(IcalTime::DateTime(time)) => Some(Deadline {
+                date: time.date(),
+                time: Some(time.time()),
+            }),
+            None => None,
+        };
+        let priority = match get("PRIORITY").map(str::parse::<u8>) {
+            Some(Ok(1..=4)) => Some(Priority::High),
+            Some(Ok(5)) => Some(Priority::Medium),
+            Some(Ok(6..=9)) => Some(Priority::Low),
+            Some(Ok(_)) | None => None,
+            Some(Err(_)) => return Err(Error::Parse("iCal priority")),
+        };
+        let pomodoro = match get("X-TIMARU-POMODORO") {
+            Some(value) => {
+                let mut counts = value.splitn(2, '/').map(str::parse::<u8>);
+                match (counts.next(), counts.next()) {
+                    (Some(Ok(done)), Some(Ok(total))) => Some((total, done)),
+                    _ => return Err(Error::Parse("iCal pomodoro")),
+                }
+            }
+            None => None,
+        };
+
+        let task = Task {
+            id: get("UID").unwrap_or_default().to_string(),
+            priority,
+            deadline,
+            pomodoro,
+            dependencies: get("X-TIMARU-AFTER")
+                .map(|ids| ids.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            notes: get("DESCRIPTION")
+                .map(|notes| unescape_text(notes).lines().map(String::from).collect())
+                .unwrap_or_default(),
+            finished: get("STATUS") == Some("COMPLETED") || get("COMPLETED").is_some(),
+            done: get_time("COMPLETED")?.map(|done| done.date()),
+            tz,
+            ..Task::new(time, unescape_text(get("SUMMARY").unwrap_or_default()))
+        };
+        Ok((date, task))
+    }
+}
+
+mod test {
+    #[test]
+    fn test_ical_round_trip() {
+        use std::str::FromStr;
+
+        use chrono::{Local, TimeZone};
+
+        use crate::task::{Priority, Task, TaskTime, Zone};
+
+        let date = Local.ymd(2026, 10, 19);
+        let mut task = Task::new(
+            TaskTime::Period {
+                start: date.and_hms(9, 0, 0),
+                end: date.and_hms(10, 30, 0),
+            },
+            "write report; then review, with a long description that has to be folded".into(),
+        );
+        task.priority = Some(Priority::Medium);
+        task.pomodoro = Some((4, 1));
+        task.notes = vec!["first line".into(), "second line".into()];
+
+        let (parsed_date, parsed) = Task::from_ical(&task.as_ical(&date)).unwrap();
+        assert_eq!(parsed_date, date);
+        assert_eq!(parsed.as_string(), task.as_string());
+
+        // A task late in the evening in New York is on the next day in most other zones.
+        let zone = Zone::from_str("America/New_York").unwrap();
+        let mut task = Task::new(
+            TaskTime::from_str_in("23:30", &date, Some(&zone)).unwrap(),
+            "call".into(),
+        );
+        task.tz = Some(zone);
+        let ical = task.as_ical(&date);
+        assert!(ical.contains("X-TIMARU-TIME:23:30\r\n"));
+        assert!(ical.contains("X-TIMARU-TZ:America/New_York\r\n"));
+        let (parsed_date, parsed) = Task::from_ical(&ical).unwrap();
+        assert_eq!(parsed_date, date);
+        assert_eq!(parsed.as_string(), task.as_string());
+    }
+}
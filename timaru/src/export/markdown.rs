@@ -0,0 +1,109 @@
+use chrono::Datelike;
+
+use crate::{format::formats, parser::ChildrenLayout, schedule::Schedule, task::Task};
+
+/// Subtasks are nested below their task and notes quoted, leaving out tracked time.
+const MARKDOWN_LAYOUT: ChildrenLayout = ChildrenLayout {
+    indent: "  ",
+    note: '>',
+    intervals: false,
+};
+
+impl Schedule {
+    /// Renders the schedule as a markdown agenda, with a heading for the day followed by a
+    /// checkbox list of its tasks.
+    pub fn as_markdown(&self) -> String {
+        let mut s = format!(
+            "## {} {}\n\n",
+            self.date.weekday(),
+            formats().date(self.date.naive_local())
+        );
+
+        for (_, task) in self.sorted_tasks() {
+            s += &task.as_markdown();
+        }
+
+        s
+    }
+}
+
+impl Task {
+    pub fn as_markdown(&self) -> String {
+        let mut s = format!(
+            "- [{}] **{}** {}",
+            if self.finished { 'X' } else { ' ' },
+            self.time.as_string_with(self.tz.as_ref(), &formats()),
+            self.description
+        );
+
+        let mut details = Vec::new();
+        if let Some(priority) = self.priority {
+            details.push(format!("priority {}", priority.as_str()));
+        }
+        if let Some((total, done)) = self.pomodoro {
+            details.push(format!("pomodoro {}/{}", done, total));
+        }
+        if let Some(deadline) = &self.deadline {
+            details.push(format!("due {}", deadline.as_string_with(&formats())));
+        }
+        if let Some(tz) = &self.tz {
+            details.push(format!("tz {}", tz.as_string()));
+        }
+        if !details.is_empty() {
+            s += &format!(" _({})_", details.join(", "));
+        }
+        s += &self.children_as_string_with(&MARKDOWN_LAYOUT);
+        s += "\n";
+
+        s
+    }
+}
+
+/// Renders the schedules as a single markdown agenda, leaving out days without tasks.
+pub fn schedules_as_markdown(schedules: &[Schedule]) -> String {
+    let mut s = String::from("# Agenda\n\n");
+    for schedule in schedules
+        .iter()
+        .filter(|schedule| !schedule.tasks.is_empty())
+    {
+        s += &schedule.as_markdown();
+        s += "\n";
+    }
+    s
+}
+
+mod test {
+    #[test]
+    fn test_markdown() {
+        use std::env;
+
+        use crate::schedule::Schedule;
+
+        let file = env::temp_dir().join(format!("timaru-test-markdown-{}", std::process::id()));
+        let mut schedule = Schedule::from_str(
+            file,
+            "# 19-10-2026 v3
+* [ ] 9:00 - 10:30 {id:1 priority:A} => write report
+    - [ ] outline
+    > * not a heading
+    >
+    > done by noon
+* [X] 14:00 {id:2} => stretch",
+        )
+        .unwrap();
+        schedule.read_only = true;
+
+        assert_eq!(
+            schedule.as_markdown(),
+            "## Mon 19-10-2026
+
+- [ ] **9:00 - 10:30** write report _(priority A)_
+  - [ ] outline
+  > * not a heading
+  >
+  > done by noon
+- [X] **14:00** stretch
+"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::{Date, Local};
+
+use crate::{error::Error, format::formats, schedule::Schedule, task::Task};
+
+mod csv;
+mod html;
+mod ics;
+mod markdown;
+mod org;
+mod todotxt;
+
+pub use self::csv::*;
+pub use html::*;
+pub use markdown::*;
+pub use org::*;
+pub use todotxt::*;
+
+/// Creates or updates `tasks`, each along with the date of its schedule, printing what is done
+/// for each of them. A task with the id of an existing task is replaced by `merge(existing,
+/// imported)`, moving it to the new date if needed, which lets formats keep the fields they don't
+/// store. Other tasks are created, with a new id if theirs is empty. Finished tasks without a
+/// completion date are finished today, like tasks finished with `timaru update`. Nothing is
+/// changed if `dry_run` is set.
+pub(crate) async fn import_tasks(
+    db_dir: &Path,
+    tasks: Vec<(Date<Local>, Task)>,
+    dry_run: bool,
+    merge: impl Fn(Task, Task) -> Task,
+) -> Result<(), Error> {
+    let mut existing: HashMap<_, _> = Schedule::all_tasks(db_dir)
+        .await?
+        .into_iter()
+        .map(|(date, _, task)| (task.id, date))
+        .collect();
+
+    for (date, mut task) in tasks {
+        let date_string = formats().date(date.naive_local());
+        let old_date = existing.get(&task.id).copied();
+        match old_date {
+            Some(_) => println!("update {} {} {}", date_string, task.id, task.description),
+            None => println!("create {} {}", date_string, task.description),
+        }
+        if dry_run {
+            continue;
+        }
+
+        // The task is only taken out of its old schedule once it is added to the new one, so that
+        // it is never lost.
+        let mut schedule = Schedule::open(db_dir, date).await?;
+        let mut old_schedule = match old_date {
+            Some(old_date) if old_date != date => Some(Schedule::open(db_dir, old_date).await?),
+            _ => None,
+        };
+        let mut old_idx = None;
+        let mut task = match old_date {
+            Some(_) => {
+                let old = old_schedule.as_ref().unwrap_or(&schedule);
+                old_idx = old.find_task(&task.id);
+                match old_idx.and_then(|idx| old.tasks.get(&idx)) {
+                    Some(old_task) => merge(old_task.clone(), task),
+                    None => return Err(Error::NoTask(task.id)),
+                }
+            }
+            None => {
+                if task.id.is_empty() {
+                    task.id = Task::new_id();
+                }
+                task
+            }
+        };
+        task.done = match task.finished {
+            true => task.done.or_else(|| Some(Local::today())),
+            false => None,
+        };
+
+        existing.insert(task.id.clone(), date);
+        schedule.add_task(task)?;
+        if let Some(idx) = old_idx {
+            old_schedule
+                .as_mut()
+                .unwrap_or(&mut schedule)
+                .remove_task(idx);
+        }
+    }
+
+    Ok(())
+}
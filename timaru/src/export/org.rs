@@ -0,0 +1,153 @@
+use chrono::{Date, Datelike, Local, NaiveTime, Timelike};
+
+use crate::{
+    parser::ChildrenLayout,
+    schedule::Schedule,
+    task::{Task, TaskTime, Zone},
+};
+
+/// Subtasks are checkboxes below the properties of their task, and notes fixed-width lines so that
+/// nothing in them is read as org markup, like a `*` starting a heading. Tracked time is left out.
+const ORG_LAYOUT: ChildrenLayout = ChildrenLayout {
+    indent: "   ",
+    note: ':',
+    intervals: false,
+};
+
+/// An org-mode timestamp such as `<2026-10-19 Mon 09:00-10:30>`.
+fn org_timestamp(date: &Date<Local>, start: Option<NaiveTime>, end: Option<NaiveTime>) -> String {
+    let mut s = format!(
+        "<{}-{:02}-{:02} {}",
+        date.year(),
+        date.month(),
+        date.day(),
+        date.weekday()
+    );
+    if let Some(start) = start {
+        s += &format!(" {:02}:{:02}", start.hour(), start.minute());
+        if let Some(end) = end {
+            s += &format!("-{:02}:{:02}", end.hour(), end.minute());
+        }
+    }
+    s += ">";
+    s
+}
+
+impl Schedule {
+    /// Renders the schedule as an org-mode heading for the day, with a `TODO` or `DONE` heading
+    /// below it for each task.
+    pub fn as_org(&self) -> String {
+        let mut s = format!("* {}\n", org_timestamp(&self.date, None, None));
+        for (_, task) in self.sorted_tasks() {
+            s += &task.as_org(&self.date);
+        }
+        s
+    }
+}
+
+impl Task {
+    pub fn as_org(&self, date: &Date<Local>) -> String {
+        let mut s = format!("** {} ", if self.finished { "DONE" } else { "TODO" });
+        if let Some(priority) = self.priority {
+            s += &format!("[#{}] ", priority.as_str());
+        }
+        s += &self.description;
+        if !self.subtasks.is_empty() {
+            let done = self
+                .subtasks
+                .iter()
+                .filter(|subtask| subtask.finished)
+                .count();
+            s += &format!(" [{}/{}]", done, self.subtasks.len());
+        }
+        s += "\n";
+
+        // Times of day have no precise time, so only the date is scheduled for them.
+        let wall_clock = |time| Some(Zone::wall_clock_of(self.tz.as_ref(), time).time());
+        let (start, end) = match &self.time {
+            TaskTime::Precise { time } => (wall_clock(time), None),
+            TaskTime::Period { start, end } => (wall_clock(start), wall_clock(end)),
+            TaskTime::General { .. } | TaskTime::GeneralPeriod { .. } => (None, None),
+        };
+        s += &format!("   SCHEDULED: {}", org_timestamp(date, start, end));
+        if let Some(deadline) = &self.deadline {
+            s += &format!(
+                " DEADLINE: {}",
+                org_timestamp(&deadline.date, deadline.time, None)
+            );
+        }
+        s += "\n";
+
+        s += &format!("   :PROPERTIES:\n   :ID: {}\n", self.id);
+        if let TaskTime::General { .. } | TaskTime::GeneralPeriod { .. } = self.time {
+            s += &format!("   :TIME: {}\n", self.time.as_string());
+        }
+        if let Some(tz) = &self.tz {
+            s += &format!("   :TZ: {}\n", tz.as_string());
+        }
+        if let Some((total, done)) = self.pomodoro {
+            s += &format!("   :POMODORO: {}/{}\n", done, total);
+        }
+        s += "   :END:";
+        s += &self.children_as_string_with(&ORG_LAYOUT);
+        s += "\n";
+
+        s
+    }
+}
+
+/// Renders the schedules as a single org-mode file, leaving out days without tasks.
+pub fn schedules_as_org(schedules: &[Schedule]) -> String {
+    let mut s = String::from("#+TITLE: Agenda\n\n");
+    for schedule in schedules
+        .iter()
+        .filter(|schedule| !schedule.tasks.is_empty())
+    {
+        s += &schedule.as_org();
+    }
+    s
+}
+
+mod test {
+    #[test]
+    fn test_org() {
+        use std::env;
+
+        use crate::schedule::Schedule;
+
+        let file = env::temp_dir().join(format!("timaru-test-org-{}", std::process::id()));
+        let mut schedule = Schedule::from_str(
+            file,
+            "# 19-10-2026 v3
+* [ ] 9:00 - 10:30 {id:1 priority:A} => write report
+    - [ ] outline
+    > * not a heading
+    >
+    > done by noon
+* [X] 14:00 {id:2} => stretch",
+        )
+        .unwrap();
+        schedule.read_only = true;
+
+        // Notes are fixed-width lines, so the `*` starting one is not a heading.
+        assert_eq!(
+            schedule.as_org(),
+            "* <2026-10-19 Mon>
+** TODO [#A] write report [0/1]
+   SCHEDULED: <2026-10-19 Mon 09:00-10:30>
+   :PROPERTIES:
+   :ID: 1
+   :END:
+   - [ ] outline
+   : * not a heading
+   :
+   : done by noon
+** DONE stretch
+   SCHEDULED: <2026-10-19 Mon 14:00>
+   :PROPERTIES:
+   :ID: 2
+   :END:
+"
+        );
+    }
+}
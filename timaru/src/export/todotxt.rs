@@ -0,0 +1,319 @@
+//! todo.txt export and import of schedules.
+//!
+//! Each task is one line of the form `[x] [(A)] [date] description [key:value...]`, the date
+//! being the date of the schedule of the task. Finished tasks start with `x` followed by the date
+//! on which they were finished and the date of their schedule, and keep their priority in a
+//! `pri:` tag, as todo.txt only allows priorities on unfinished tasks. `+project` and `@context`
+//! tags are kept in the description. The other timaru fields are stored as `key:value` tags:
+//!
+//! - `time:9:00-10:30` or `time:morning`, the task time written as in schedule files, with the
+//!   spaces of custom times of day escaped as `%20`
+//! - `tz:Europe/Paris` or `tz:+02:00`, the timezone on whose clocks the time is, the local one if
+//!   the tag is missing
+//! - `pomodoro:1/4`, pomodoro cycles done out of the total
+//! - `due:2026-10-20`, or `due:2026-10-20T17:30` if the deadline has a time, followed by its
+//!   seconds if it has any, as in `due:2026-10-20T17:30:15`
+//! - `after:id1,id2`, ids of the tasks this task depends on
+//! - `id:1a2b3c4d`, id of the task
+
+use std::{path::Path, str::FromStr};
+
+use chrono::{Date, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+
+use super::import_tasks;
+use crate::{
+    error::Error,
+    format::Formats,
+    task::{Deadline, Priority, Task, TaskTime, Zone},
+};
+
+/// Formats `date` as `yyyy-mm-dd`, the date format of todo.txt.
+fn todo_date(date: &Date<Local>) -> String {
+    format!("{}-{:02}-{:02}", date.year(), date.month(), date.day())
+}
+
+/// Formats a deadline time as `hh:mm`, or `hh:mm:ss` if it has seconds.
+fn todo_time(time: &NaiveTime) -> String {
+    if time.second() != 0 {
+        time.format("%H:%M:%S").to_string()
+    } else {
+        time.format("%H:%M").to_string()
+    }
+}
+
+/// Parses a `hh:mm` or `hh:mm:ss` time.
+fn parse_todo_time(input: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(input, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(input, "%H:%M"))
+        .ok()
+}
+
+/// Escapes the spaces of a task time, which would end the `time:` tag.
+fn escape_time(time: &str) -> String {
+    time.replace('%', "%25").replace(' ', "%20")
+}
+
+fn unescape_time(time: &str) -> String {
+    time.replace("%20", " ").replace("%25", "%")
+}
+
+/// Parses a `yyyy-mm-dd` date.
+fn parse_todo_date(input: &str) -> Option<Date<Local>> {
+    let date = NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?;
+    Local.from_local_date(&date).single()
+}
+
+impl Task {
+    pub fn as_todo_txt(&self, date: &Date<Local>) -> String {
+        let mut fields = Vec::new();
+
+        if self.finished {
+            fields.push("x".to_string());
+            fields.push(todo_date(self.done.as_ref().unwrap_or(date)));
+        } else if let Some(priority) = self.priority {
+            fields.push(format!("({})", priority.as_str()));
+        }
+        fields.push(todo_date(date));
+        fields.push(self.description.clone());
+
+        if let (true, Some(priority)) = (self.finished, self.priority) {
+            fields.push(format!("pri:{}", priority.as_str()));
+        }
+        let (_, start, end) = self
+            .time
+            .as_csv_fields_with(self.tz.as_ref(), &Formats::default());
+        if end.is_empty() {
+            fields.push(format!("time:{}", escape_time(&start)));
+        } else {
+            fields.push(format!(
+                "time:{}-{}",
+                escape_time(&start),
+                escape_time(&end)
+            ));
+        }
+        if let Some(tz) = &self.tz {
+            fields.push(format!("tz:{}", tz.as_string()));
+        }
+        if let Some((total, done)) = self.pomodoro {
+            fields.push(format!("pomodoro:{}/{}", done, total));
+        }
+        if let Some(deadline) = &self.deadline {
+            match deadline.time {
+                Some(time) => fields.push(format!(
+                    "due:{}T{}",
+                    todo_date(&deadline.date),
+                    todo_time(&time)
+                )),
+                None => fields.push(format!("due:{}", todo_date(&deadline.date))),
+            }
+        }
+        if !self.dependencies.is_empty() {
+            fields.push(format!("after:{}", self.dependencies.join(",")));
+        }
+        fields.push(format!("id:{}", self.id));
+
+        fields.join(" ")
+    }
+
+    /// Parses a todo.txt line into a task and the date of its schedule, which is the creation
+    /// date of the line, or today if it has none. Tasks without a `time:` tag are planned at the
+    /// current time, like tasks added without a time. The task has an empty id if the line has
+    /// no `id:` tag.
+    pub fn from_todo_txt(line: &str) -> Result<(Date<Local>, Task), Error> {
+        let mut words = line.split_whitespace().peekable();
+
+        let finished = words.peek() == Some(&"x");
+        if finished {
+            words.next();
+        }
+
+        let mut priority = None;
+        if let Some(word) = words.peek() {
+            if word.len() == 3 && word.starts_with('(') && word.ends_with(')') {
+                priority = Some(todo_priority(&word[1..2])?);
+                words.next();
+            }
+        }
+
+        // A finished task has its completion date first, followed by its creation date.
+        let mut dates = Vec::new();
+        while let Some(date) = words.peek().and_then(|word| parse_todo_date(word)) {
+            dates.push(date);
+            words.next();
+        }
+        let (date, done) = match (finished, dates.as_slice()) {
+            (_, []) => (Local::today(), None),
+            (true, [done, created, ..]) => (*created, Some(*done)),
+            (_, [date, ..]) => (*date, None),
+        };
+
+        let mut description = Vec::new();
+        let mut time = None;
+        let mut tz = None;
+        let mut pomodoro = None;
+        let mut deadline = None;
+        let mut dependencies = Vec::new();
+        let mut id = String::new();
+
+        for word in words {
+            let mut parts = word.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some("pri"), Some(value)) => priority = Some(todo_priority(value)?),
+                (Some("time"), Some(value)) => time = Some(unescape_time(value)),
+                (Some("tz"), Some(value)) => tz = Some(Zone::from_str(value)?),
+                (Some("pomodoro"), Some(value)) => {
+                    let mut counts = value.splitn(2, '/').map(|count| count.parse::<u8>());
+                    pomodoro = match (counts.next(), counts.next()) {
+                        (Some(Ok(done)), Some(Ok(total))) => Some((total, done)),
+                        _ => return Err(Error::Parse("todo.txt pomodoro")),
+                    };
+                }
+                (Some("due"), Some(value)) => {
+                    let mut parts = value.splitn(2, 'T');
+                    let date = parts.next().and_then(parse_todo_date);
+                    let time = parts.next().map(parse_todo_time);
+                    deadline = match (date, time) {
+                        (Some(date), None) => Some(Deadline { date, time: None }),
+                        (Some(date), Some(Some(time))) => Some(Deadline {
+                            date,
+                            time: Some(time),
+                        }),
+                        _ => return Err(Error::Parse("todo.txt due date")),
+                    };
+                }
+                (Some("after"), Some(value)) => {
+                    dependencies = value.split(',').map(String::from).collect();
+                }
+                (Some("id"), Some(value)) => id = value.to_string(),
+                _ => description.push(word),
+            }
+        }
+
+        // The time is read once all the tags are, as the `tz:` tag may come after it.
+        let time = match time {
+            Some(time) => TaskTime::from_str_in(&time, &date, tz.as_ref())?,
+            None => TaskTime::Precise {
+                time: Zone::at_wall_clock_of(
+                    None,
+                    &date.naive_local().and_time(Local::now().time()),
+                )?,
+            },
+        };
+
+        let task = Task {
+            id,
+            pomodoro,
+            priority,
+            deadline,
+            dependencies,
+            finished,
+            done,
+            tz,
+            ..Task::new(time, description.join(" "))
+        };
+        Ok((date, task))
+    }
+}
+
+/// todo.txt allows priorities from `A` to `Z`.
+fn todo_priority(input: &str) -> Result<Priority, Error> {
+    match input.chars().next() {
+        Some('A'..='Z') if input.len() == 1 => Priority::from_str(input),
+        _ => Err(Error::Parse("todo.txt priority")),
+    }
+}
+
+/// Renders the tasks, along with the dates of their schedules, as todo.txt lines.
+pub fn tasks_as_todo_txt<'a>(tasks: impl Iterator<Item = (&'a Date<Local>, &'a Task)>) -> String {
+    let mut s = String::new();
+    for (date, task) in tasks {
+        s += &format!("{}\n", task.as_todo_txt(date));
+    }
+    s
+}
+
+/// Creates or updates the tasks of the todo.txt lines in `input`, like `import_csv_rows`. The
+/// subtasks, notes and tracked time of updated tasks are kept, as todo.txt doesn't store them.
+pub async fn import_todo_txt(db_dir: &Path, input: &str, dry_run: bool) -> Result<(), Error> {
+    let tasks = input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Task::from_todo_txt)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    import_tasks(db_dir, tasks, dry_run, |old, new| Task {
+        time: new.time,
+        description: new.description,
+        pomodoro: new.pomodoro,
+        priority: new.priority,
+        deadline: new.deadline,
+        dependencies: new.dependencies,
+        finished: new.finished,
+        done: new.done.or(old.done),
+        tz: new.tz,
+        ..old
+    })
+    .await
+}
+
+mod test {
+    #[test]
+    fn test_todo_txt_round_trip() {
+        use std::str::FromStr;
+
+        use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+
+        use crate::task::{Priority, Task, TaskTime, TimeOfDay, Zone};
+
+        let line = "x 2026-10-19 2026-10-18 call mom +family @phone pri:A time:9:00-9:30 \
+                    pomodoro:1/2 due:2026-10-20T17:30 after:1a2b3c4d id:5e6f7a8b";
+        let (date, task) = Task::from_todo_txt(line).unwrap();
+
+        assert_eq!(task.description, "call mom +family @phone");
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.pomodoro, Some((2, 1)));
+        assert!(task.finished);
+        assert_eq!(task.done, Some(Local.ymd(2026, 10, 19)));
+        assert_eq!(date, Local.ymd(2026, 10, 18));
+        assert_eq!(task.as_todo_txt(&date), line);
+
+        let line = "(F) 2026-10-18 read time:after%20lunch-evening id:9c0d1e2f";
+        let (date, task) = Task::from_todo_txt(line).unwrap();
+        assert_eq!(task.priority, Some(Priority::Lower(2)));
+        assert_eq!(
+            task.time,
+            TaskTime::GeneralPeriod {
+                start: TimeOfDay::Custom("after lunch".to_string()),
+                end: TimeOfDay::Evening,
+            }
+        );
+        assert_eq!(task.as_todo_txt(&date), line);
+
+        let line = "2026-10-18 call time:22:00-23:30 tz:America/New_York id:0d1e2f3a";
+        let (date, task) = Task::from_todo_txt(line).unwrap();
+        assert_eq!(task.tz, Some(Zone::from_str("America/New_York").unwrap()));
+        match &task.time {
+            TaskTime::Period { start, .. } => assert_eq!(
+                task.tz.unwrap().wall_clock(start),
+                NaiveDate::from_ymd(2026, 10, 18).and_hms(22, 0, 0)
+            ),
+            _ => panic!("not a period"),
+        }
+        assert_eq!(task.as_todo_txt(&date), line);
+
+        // Seconds of times and deadlines are kept.
+        let line = "2026-10-18 submit time:9:00:30 due:2026-10-20T17:30:15 id:4b5c6d7e";
+        let (date, task) = Task::from_todo_txt(line).unwrap();
+        assert_eq!(
+            task.deadline.as_ref().unwrap().time,
+            Some(NaiveTime::from_hms(17, 30, 15))
+        );
+        assert_eq!(
+            task.time,
+            TaskTime::Precise {
+                time: date.and_hms(9, 0, 30)
+            }
+        );
+        assert_eq!(task.as_todo_txt(&date), line);
+    }
+}
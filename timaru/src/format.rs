@@ -0,0 +1,224 @@
+//! Dates and times as shown to the user and read from them, following the strftime patterns of
+//! the `[format]` config section, e.g. `date = "%Y-%m-%d"` for ISO dates or `time = "%-I:%M %p"`
+//! for a 12-hour clock.
+//!
+//! Schedule files and the history always use the canonical formats, `d-m-yyyy` and `h:mm`, which
+//! are also the defaults. Dates and times given in the canonical formats are read whatever the
+//! patterns, so that scripts keep working once they are changed.
+
+use std::sync::Mutex;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    Date, Local, LocalResult, NaiveDate, NaiveTime, TimeZone,
+};
+
+use crate::{
+    config::FormatConfig,
+    error::Error,
+    parser::{get_date, parse_time},
+    task::Deadline,
+};
+
+/// Canonical format of dates, as in `19-10-2026`.
+pub const DATE: &str = "%-d-%-m-%Y";
+/// Canonical format of times, as in `9:05`.
+pub const TIME: &str = "%-H:%M";
+
+/// The formats set from the config file, the canonical ones being used until then.
+static FORMATS: Mutex<Option<Formats>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formats {
+    pub date: String,
+    pub time: String,
+}
+
+impl Default for Formats {
+    fn default() -> Formats {
+        Formats {
+            date: DATE.to_string(),
+            time: TIME.to_string(),
+        }
+    }
+}
+
+/// Sets the formats used by `formats` from the `[format]` config section.
+pub fn init(config: &FormatConfig) -> Result<(), Error> {
+    let formats = Formats::new(
+        config.date.as_deref().unwrap_or(DATE),
+        config.time.as_deref().unwrap_or(TIME),
+    )?;
+    *FORMATS.lock().unwrap() = Some(formats);
+    Ok(())
+}
+
+/// The formats dates and times are shown in.
+pub fn formats() -> Formats {
+    FORMATS.lock().unwrap().clone().unwrap_or_default()
+}
+
+impl Formats {
+    pub fn new(date: &str, time: &str) -> Result<Formats, Error> {
+        for pattern in [date, time].iter() {
+            if pattern.is_empty() || StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+                return Err(Error::Format(pattern.to_string()));
+            }
+        }
+
+        Ok(Formats {
+            date: date.to_string(),
+            time: time.to_string(),
+        })
+    }
+
+    pub fn date(&self, date: NaiveDate) -> String {
+        date.format(&self.date).to_string()
+    }
+
+    /// Shows the month of `date` in the date format, leaving out the fields of the day along
+    /// with one separator next to each, as in `10-2026` or `2026-10`.
+    pub fn month(&self, date: NaiveDate) -> String {
+        // Split the pattern into specifiers, like `%-d`, and the literals between them.
+        let mut parts: Vec<String> = Vec::new();
+        let mut chars = self.date.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                let mut specifier = c.to_string();
+                while let Some(&flag) = chars.peek() {
+                    specifier.push(flag);
+                    chars.next();
+                    if !matches!(flag, '-' | '_' | '0') {
+                        break;
+                    }
+                }
+                parts.push(specifier);
+            } else {
+                match parts.last_mut() {
+                    Some(literal) if !literal.starts_with('%') => literal.push(c),
+                    _ => parts.push(c.to_string()),
+                }
+            }
+        }
+
+        let is_day = |part: &str| {
+            part.starts_with('%')
+                && matches!(
+                    part.chars().last(),
+                    Some('d' | 'e' | 'a' | 'A' | 'j' | 'u' | 'w')
+                )
+        };
+        let is_literal = |part: &String| !part.starts_with('%');
+        while let Some(idx) = parts.iter().position(|part| is_day(part)) {
+            parts.remove(idx);
+            if matches!(parts.get(idx), Some(part) if is_literal(part)) {
+                parts.remove(idx);
+            } else if idx > 0 && is_literal(&parts[idx - 1]) {
+                parts.remove(idx - 1);
+            }
+        }
+
+        date.format(&parts.concat()).to_string()
+    }
+
+    pub fn time(&self, time: NaiveTime) -> String {
+        time.format(&self.time).to_string()
+    }
+
+    /// Reads a date given in the date format, or in the canonical one.
+    pub fn parse_date(&self, input: &str) -> Result<Date<Local>, Error> {
+        match NaiveDate::parse_from_str(input.trim(), &self.date) {
+            Ok(date) => match Local.from_local_date(&date) {
+                LocalResult::Single(date) | LocalResult::Ambiguous(date, _) => Ok(date),
+                LocalResult::None => Err(Error::Parse("date can not exist")),
+            },
+            Err(_) => get_date(input),
+        }
+    }
+
+    /// Rewrites the times of a `TaskTime` given in the time format, as in `9:00 PM - 10:30 PM`,
+    /// in the canonical one, leaving anything else as it is to be read as a `TaskTime`.
+    pub fn canonical_time(&self, input: &str) -> String {
+        let parts: Vec<_> = input
+            .split('-')
+            .map(|part| {
+                (
+                    part.trim(),
+                    NaiveTime::parse_from_str(part.trim(), &self.time).ok(),
+                )
+            })
+            .collect();
+        if parts.iter().all(|(_, time)| time.is_none()) {
+            return input.to_string();
+        }
+
+        // Times which are not in the format, like ones with seconds, are kept as they are.
+        parts
+            .iter()
+            .map(|(part, time)| match time {
+                Some(time) => time.format(TIME).to_string(),
+                None => part.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" - ")
+    }
+
+    /// Reads a deadline given as a date, optionally followed by `@` and a time, both in the
+    /// formats or in the canonical ones.
+    pub fn parse_deadline(&self, input: &str) -> Result<Deadline, Error> {
+        let mut parts = input.splitn(2, '@');
+        let date = self.parse_date(parts.next().unwrap_or_default())?;
+        let time = match parts.next() {
+            Some(time) => Some(parse_time(&self.canonical_time(time), &date)?.time()),
+            None => None,
+        };
+
+        Ok(Deadline { date, time })
+    }
+}
+
+mod test {
+    #[test]
+    fn test_formats() {
+        use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+
+        use super::Formats;
+
+        let date = NaiveDate::from_ymd(2026, 10, 9);
+        let time = NaiveTime::from_hms(21, 5, 0);
+        let canonical = Formats::default();
+        assert_eq!(canonical.date(date), "9-10-2026");
+        assert_eq!(canonical.time(time), "21:05");
+        assert_eq!(canonical.month(date), "10-2026");
+
+        let formats = Formats::new("%Y-%m-%d", "%-I:%M %p").unwrap();
+        assert_eq!(formats.date(date), "2026-10-09");
+        assert_eq!(formats.time(time), "9:05 PM");
+        assert_eq!(formats.month(date), "2026-10");
+        let named = Formats::new("%a %-d %B %Y", "%H:%M").unwrap();
+        assert_eq!(named.month(date), "October 2026");
+        assert_eq!(
+            formats.parse_date("2026-10-09").unwrap(),
+            Local.ymd(2026, 10, 9)
+        );
+        assert_eq!(
+            formats.parse_date("9-10-2026").unwrap(),
+            Local.ymd(2026, 10, 9)
+        );
+        assert!(formats.parse_date("2026-13-01").is_err());
+        assert!(formats.parse_date("31-2-2026").is_err());
+        assert_eq!(formats.canonical_time("9:05 PM"), "21:05");
+        assert_eq!(formats.canonical_time("9:00 AM - 10:30 AM"), "9:00 - 10:30");
+        assert_eq!(formats.canonical_time("21:05"), "21:05");
+        assert_eq!(formats.canonical_time("morning"), "morning");
+        assert_eq!(
+            formats.canonical_time("9:00:30 - 10:30 PM"),
+            "9:00:30 - 22:30"
+        );
+        let deadline = formats.parse_deadline("2026-10-09@5:30 PM").unwrap();
+        assert_eq!(deadline.as_string(), "9-10-2026@17:30");
+        assert_eq!(deadline.as_string_with(&formats), "2026-10-09@5:30 PM");
+
+        assert!(Formats::new("%Y-%Q", "%H:%M").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+//! Integrity check of the database, run with `timaru fsck`.
+//!
+//! Every entry of `db/` is checked to be a `<year>/<month>/<day>` schedule file of an existing
+//! date. The header of each schedule has to match the date of its path, and each of its lines has
+//! to parse. Repairing a schedule fixes its header and moves the lines which do not parse to
+//! `db/quarantine/<year>-<month>-<day>`, from where they can be fixed and copied back by hand.
+//! Empty schedules, which are left behind by commands only reading a date, are removed.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Date, Datelike, Local, LocalResult, TimeZone};
+use tokio::fs;
+
+use crate::{
+    error::Error,
+    parser::{BINARY_MAGIC, TEXT_VERSION},
+    schedule::Schedule,
+    setup::check_dir,
+    storage,
+    task::{Subtask, Task, WorkInterval},
+};
+
+/// Entries of `db/` which are not schedules.
+const KNOWN_ENTRIES: &[&str] = &["ENCRYPTION", "FORMAT", "VERSION", ".git", "quarantine"];
+
+/// The problems found in an entry of the database, and how to repair them if they can be.
+#[derive(Debug)]
+pub struct Report {
+    /// Path of the entry, relative to the database directory.
+    pub path: PathBuf,
+    /// Problems found, each prefixed with the number of the line it is on if any.
+    pub problems: Vec<String>,
+    pub repair: Option<Repair>,
+}
+
+#[derive(Debug)]
+pub enum Repair {
+    /// Removes an empty schedule file.
+    Remove,
+    /// Writes a schedule as `text` and quarantines the lines which do not parse.
+    Rewrite {
+        text: String,
+        quarantined: Vec<String>,
+    },
+}
+
+impl Repair {
+    /// A short description of the repair, to be shown before applying it.
+    pub fn as_string(&self) -> String {
+        match self {
+            Repair::Remove => "remove the file".to_string(),
+            Repair::Rewrite { quarantined, .. } if quarantined.is_empty() => {
+                "rewrite the header".to_string()
+            }
+            Repair::Rewrite { quarantined, .. } => {
+                format!("quarantine {} line(s)", quarantined.len())
+            }
+        }
+    }
+}
+
+/// The message of a parsing error, without the prefix of its description.
+fn reason(err: &Error) -> String {
+    match err {
+        Error::Parse(reason) => reason.to_string(),
+        err => err.to_string().trim_start_matches("error: ").to_string(),
+    }
+}
+
+/// Entries of `dir`, along with their name if it is a number.
+async fn entries(dir: &Path) -> Result<Vec<(PathBuf, Option<u32>)>, Error> {
+    let mut entries = Vec::new();
+    let mut read_dir = fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let number = entry.file_name().to_str().and_then(|s| s.parse().ok());
+        entries.push((entry.path(), number));
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Checks every entry of `db_dir`, returning the ones which have problems.
+pub async fn check(db_dir: &Path) -> Result<Vec<Report>, Error> {
+    let mut reports = Vec::new();
+    let mut report = |path: &Path, problem: &str| {
+        reports.push(Report {
+            path: path.strip_prefix(db_dir).unwrap_or(path).to_path_buf(),
+            problems: vec![problem.to_string()],
+            repair: None,
+        })
+    };
+
+    let mut schedules = Vec::new();
+    for (year_dir, year) in entries(db_dir).await? {
+        let name = year_dir.file_name().unwrap_or_default().to_string_lossy();
+        match year {
+            _ if KNOWN_ENTRIES.contains(&name.as_ref()) => continue,
+            Some(year) if year_dir.is_dir() => {
+                for (month_dir, month) in entries(&year_dir).await? {
+                    match month {
+                        Some(month @ 1..=12) if month_dir.is_dir() => {
+                            for (path, day) in entries(&month_dir).await? {
+                                match day.map(|day| Local.ymd_opt(year as i32, month, day)) {
+                                    Some(LocalResult::Single(date)) if path.is_file() => {
+                                        schedules.push((path, date))
+                                    }
+                                    _ => report(&path, "not a day of the month"),
+                                }
+                            }
+                        }
+                        _ => report(&month_dir, "not a month directory"),
+                    }
+                }
+            }
+            _ => report(&year_dir, "not a year directory"),
+        }
+    }
+
+    for (path, date) in schedules {
+        if let Some(report) = check_schedule(db_dir, &path, &date).await? {
+            reports.push(report);
+        }
+    }
+
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(reports)
+}
+
+/// Checks the schedule file at `path`, which should be the schedule of `date`.
+async fn check_schedule(
+    db_dir: &Path,
+    path: &Path,
+    date: &Date<Local>,
+) -> Result<Option<Report>, Error> {
+    let mut report = Report {
+        path: path.strip_prefix(db_dir).unwrap_or(path).to_path_buf(),
+        problems: Vec::new(),
+        repair: None,
+    };
+
+    let content = match storage::decode(path, fs::read(path).await?) {
+        Ok(content) => content,
+        Err(err) => {
+            report.problems.push(reason(&err));
+            return Ok(Some(report));
+        }
+    };
+
+    if content.starts_with(BINARY_MAGIC) {
+        match Schedule::from_bytes(path.into(), &content) {
+            Ok(mut schedule) if schedule.date != *date => {
+                report
+                    .problems
+                    .push("date of the schedule does not match".into());
+                schedule.date = *date;
+                report.repair = Some(Repair::Rewrite {
+                    text: schedule.as_string(),
+                    quarantined: Vec::new(),
+                });
+            }
+            Ok(_) => return Ok(None),
+            Err(err) => report.problems.push(reason(&err)),
+        }
+        return Ok(Some(report));
+    }
+
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => {
+            report.problems.push("not valid UTF-8".into());
+            return Ok(Some(report));
+        }
+    };
+    if content.trim().is_empty() {
+        report.problems.push("empty file".into());
+        report.repair = Some(Repair::Remove);
+        return Ok(Some(report));
+    }
+
+    // The header is the first line which is not blank.
+    let lines: Vec<_> = content.lines().collect();
+    let mut start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or(0);
+    let header = lines[start];
+    let mut version = 1;
+
+    match header.strip_prefix('#').map(str::split_whitespace) {
+        Some(mut parts) => {
+            let header_date = parts.next().map(|date| {
+                let numbers: Vec<_> = date.split('-').map(str::parse::<i32>).collect();
+                match numbers.as_slice() {
+                    [Ok(day), Ok(month), Ok(year)] => (*day, *month, *year),
+                    _ => (0, 0, 0),
+                }
+            });
+            let mut header_ok =
+                header_date == Some((date.day() as i32, date.month() as i32, date.year()));
+            if let Some(part) = parts.next() {
+                match part.strip_prefix('v').and_then(|v| v.parse().ok()) {
+                    Some(v) => version = v,
+                    None => header_ok = false,
+                }
+            }
+            if !header_ok {
+                report
+                    .problems
+                    .push(format!("header `{}` does not match the date", header));
+            }
+            start += 1;
+        }
+        // The first line is then checked as any other line.
+        None => report.problems.push("no `# dd-mm-yyyy` header".into()),
+    }
+    if version > TEXT_VERSION {
+        report.problems.push(reason(&Error::NewerVersion(version)));
+        return Ok(Some(report));
+    }
+
+    let mut kept = Vec::new();
+    let mut quarantined = Vec::new();
+    // Whether the last task parsed, indented lines belonging to it.
+    let mut task_ok = None;
+    for (idx, line) in lines.iter().enumerate().skip(start) {
+        let parsed = if line.trim().is_empty() {
+            Ok(())
+        } else if line.starts_with(char::is_whitespace) {
+            let line_trimmed = line.trim_start();
+            match task_ok {
+                None => Err(Error::Parse("indented line without a task")),
+                // Lines of a task which does not parse are quarantined along with it, without
+                // being reported again.
+                Some(false) => {
+                    quarantined.push(line.to_string());
+                    continue;
+                }
+                Some(true) if line_trimmed.starts_with('>') => Ok(()),
+                Some(true) if line_trimmed.starts_with('@') => {
+                    WorkInterval::from_str(line_trimmed).map(|_| ())
+                }
+                Some(true) => Subtask::from_str(line).map(|_| ()),
+            }
+        } else {
+            let task = Task::from_str(line, date).map(|_| ());
+            task_ok = Some(task.is_ok());
+            task
+        };
+
+        match parsed {
+            Ok(()) => kept.push(*line),
+            Err(err) => {
+                report
+                    .problems
+                    .push(format!("line {}: {}: {}", idx + 1, reason(&err), line));
+                quarantined.push(line.to_string());
+            }
+        }
+    }
+
+    if report.problems.is_empty() {
+        return Ok(None);
+    }
+    let text = format!(
+        "# {}-{}-{} v{}\n{}\n",
+        date.day(),
+        date.month(),
+        date.year(),
+        version,
+        kept.join("\n")
+    );
+    report.repair = Some(Repair::Rewrite { text, quarantined });
+    Ok(Some(report))
+}
+
+/// Applies the repair of `report` to the database in `db_dir`.
+pub async fn repair(db_dir: &Path, report: &Report) -> Result<(), Error> {
+    let path = db_dir.join(&report.path);
+    match &report.repair {
+        None => {}
+        Some(Repair::Remove) => fs::remove_file(&path).await?,
+        Some(Repair::Rewrite { text, quarantined }) => {
+            if !quarantined.is_empty() {
+                let name = report
+                    .path
+                    .iter()
+                    .map(|part| part.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("-");
+                let quarantine = check_dir(db_dir.join("quarantine")).await?.join(name);
+
+                let mut content = match fs::read(&quarantine).await {
+                    Ok(content) => storage::decode(&quarantine, content)?,
+                    Err(_) => Vec::new(),
+                };
+                for line in quarantined {
+                    content.extend_from_slice(line.as_bytes());
+                    content.push(b'\n');
+                }
+                fs::write(&quarantine, storage::seal(&quarantine, content)).await?;
+            }
+
+            Schedule::from_str(path, text)?.sync().await?;
+        }
+    }
+    Ok(())
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_fsck_repair() {
+        use std::env;
+
+        use tokio::fs;
+
+        use super::{check, repair};
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-fsck-{}", std::process::id()));
+        fs::create_dir_all(db_dir.join("2026/10")).await.unwrap();
+        fs::write(
+            db_dir.join("2026/10/19"),
+            "# 18-10-2026 v2
+* [ ] 9:00 {id:1} => fine
+* [ ] 10:00 (x, y) {id:2} => broken
+    - [ ] part of broken
+* [ ] noon {id:3} => also fine",
+        )
+        .await
+        .unwrap();
+        fs::write(db_dir.join("2026/10/20"), "").await.unwrap();
+
+        let reports = check(&db_dir).await.unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0].problems,
+            vec![
+                "header `# 18-10-2026 v2` does not match the date",
+                "line 3: pomodoro: * [ ] 10:00 (x, y) {id:2} => broken",
+            ]
+        );
+        assert_eq!(reports[1].problems, vec!["empty file"]);
+
+        for report in reports.iter() {
+            repair(&db_dir, report).await.unwrap();
+        }
+        assert!(check(&db_dir).await.unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(db_dir.join("2026/10/19")).await.unwrap(),
+            "# 19-10-2026 v3\n* [ ] 9:00 {id:1} => fine\n* [ ] noon {id:3} => also fine\n"
+        );
+        assert_eq!(
+            fs::read_to_string(db_dir.join("quarantine/2026-10-19"))
+                .await
+                .unwrap(),
+            "* [ ] 10:00 (x, y) {id:2} => broken\n    - [ ] part of broken\n"
+        );
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::{Date, Datelike, Duration, Local, TimeZone};
+use crossterm::style::{style, Color};
+
+use crate::{error::Error, format::formats, schedule::Schedule};
+
+/// Colours for each intensity level, from no activity to the most activity.
+const LEVEL_COLOURS: [(u8, u8, u8); 5] = [
+    (0xeb, 0xed, 0xf0),
+    (0x9b, 0xe9, 0xa8),
+    (0x40, 0xc4, 0x63),
+    (0x30, 0xa1, 0x4e),
+    (0x21, 0x6e, 0x39),
+];
+
+pub(crate) const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Activity on each day of a year, laid out as a grid of weeks (columns) and weekdays (rows).
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    pub year: i32,
+    /// Finished tasks, or done pomodoros, on each day of the year up to the last day read.
+    pub counts: Vec<(Date<Local>, u32)>,
+    /// The highest of `counts`.
+    max: u32,
+}
+
+impl Heatmap {
+    /// Reads the schedules of `year`, up to today if it is the current year. Counts done
+    /// pomodoros instead of finished tasks if `pomodoro` is set.
+    pub async fn collect(db_dir: &Path, year: i32, pomodoro: bool) -> Result<Heatmap, Error> {
+        let start_date = Local.ymd(year, 1, 1);
+        let end_date = Local
+            .ymd(year + 1, 1, 1)
+            .min(Local::today() + Duration::days(1));
+
+        // Only the days with a schedule are read, so that no file is created for the others.
+        let mut stored = HashMap::new();
+        for date in Schedule::stored_dates(db_dir).await? {
+            if date < start_date || date >= end_date {
+                continue;
+            }
+            let schedule = Schedule::read(db_dir, date).await?;
+            let count = schedule
+                .tasks
+                .values()
+                .map(|task| match (pomodoro, task.pomodoro) {
+                    (true, Some((_, done))) => done as u32,
+                    (true, None) => 0,
+                    (false, _) => task.finished as u32,
+                })
+                .sum();
+            stored.insert(date, count);
+        }
+
+        let mut counts = Vec::new();
+        let mut date = start_date;
+        while date < end_date {
+            counts.push((date, stored.get(&date).copied().unwrap_or(0)));
+            date = date.succ();
+        }
+
+        Ok(Heatmap::new(year, counts))
+    }
+
+    /// A heatmap of `year` with the `counts` of its days.
+    pub fn new(year: i32, counts: Vec<(Date<Local>, u32)>) -> Heatmap {
+        let max = counts.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        Heatmap { year, counts, max }
+    }
+
+    /// Intensity level of `count`, from 0 to 4, relative to the busiest day.
+    fn level(&self, count: u32) -> usize {
+        if count == 0 || self.max == 0 {
+            0
+        } else {
+            (count * 4).div_ceil(self.max).min(4) as usize
+        }
+    }
+
+    /// Position of `date` in the grid as (week, weekday), weeks starting on Monday.
+    fn position(date: &Date<Local>) -> (usize, usize) {
+        let first_weekday = Local
+            .ymd(date.year(), 1, 1)
+            .weekday()
+            .num_days_from_monday();
+        let weekday = date.weekday().num_days_from_monday();
+        (
+            ((date.ordinal0() + first_weekday) / 7) as usize,
+            weekday as usize,
+        )
+    }
+
+    /// Renders the grid for a terminal, using colours for the intensity of each day.
+    pub fn as_terminal_string(&self) -> String {
+        let mut grid = vec![vec![None; 54]; 7];
+        for (date, count) in self.counts.iter() {
+            let (week, weekday) = Heatmap::position(date);
+            grid[weekday][week] = Some(self.level(*count));
+        }
+
+        let mut month_labels = vec![' '; 54 * 2 + 4];
+        for (month, name) in MONTHS.iter().enumerate() {
+            let (week, _) = Heatmap::position(&Local.ymd(self.year, month as u32 + 1, 1));
+            for (i, c) in name.chars().enumerate() {
+                if let Some(label) = month_labels.get_mut(4 + week * 2 + i) {
+                    *label = c;
+                }
+            }
+        }
+
+        let mut s = format!("{}\n", self.year);
+        s += month_labels.into_iter().collect::<String>().trim_end();
+        s += "\n";
+
+        for (weekday, row) in grid.iter().enumerate() {
+            let mut line = String::from(match weekday {
+                0 => "Mon ",
+                2 => "Wed ",
+                4 => "Fri ",
+                _ => "    ",
+            });
+            for cell in row.iter() {
+                match cell {
+                    Some(level) => {
+                        let (r, g, b) = LEVEL_COLOURS[*level];
+                        line += &format!("{} ", style("■").with(Color::Rgb { r, g, b }));
+                    }
+                    None => line += "  ",
+                }
+            }
+            s += line.trim_end();
+            s += "\n";
+        }
+
+        s
+    }
+
+    /// Renders the grid as a standalone SVG image.
+    pub fn as_svg(&self) -> String {
+        const CELL: usize = 11;
+        const STEP: usize = 13;
+        const LEFT: usize = 30;
+        const TOP: usize = 20;
+
+        let mut s = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"sans-serif\" font-size=\"10\">\n",
+            LEFT + 54 * STEP,
+            TOP + 7 * STEP
+        );
+
+        for (month, name) in MONTHS.iter().enumerate() {
+            let (week, _) = Heatmap::position(&Local.ymd(self.year, month as u32 + 1, 1));
+            s += &format!(
+                "<text x=\"{}\" y=\"{}\">{}</text>\n",
+                LEFT + week * STEP,
+                TOP - 6,
+                name
+            );
+        }
+        for (weekday, name) in [(0, "Mon"), (2, "Wed"), (4, "Fri")].iter() {
+            s += &format!(
+                "<text x=\"0\" y=\"{}\">{}</text>\n",
+                TOP + weekday * STEP + CELL - 1,
+                name
+            );
+        }
+
+        for (date, count) in self.counts.iter() {
+            let (week, weekday) = Heatmap::position(date);
+            let (r, g, b) = LEVEL_COLOURS[self.level(*count)];
+            s += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"2\" \
+                 fill=\"#{:02x}{:02x}{:02x}\"><title>{}: {}</title></rect>\n",
+                LEFT + week * STEP,
+                TOP + weekday * STEP,
+                CELL,
+                CELL,
+                r,
+                g,
+                b,
+                formats().date(date.naive_local()),
+                count
+            );
+        }
+
+        s += "</svg>\n";
+        s
+    }
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_heatmap_collect() {
+        use std::env;
+
+        use chrono::{Local, TimeZone};
+        use tokio::fs;
+
+        use super::Heatmap;
+        use crate::schedule::Schedule;
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-heatmap-{}", std::process::id()));
+        fs::create_dir_all(db_dir.join("2025/3")).await.unwrap();
+        fs::write(
+            db_dir.join("2025/3/4"),
+            "# 04-03-2025 v3
+* [X] 9:00 (2, 2) {id:1} => write report
+* [X] 10:00 {id:2} => call mum
+* [ ] 11:00 (3, 1) {id:3} => read",
+        )
+        .await
+        .unwrap();
+        fs::write(
+            db_dir.join("2025/3/5"),
+            "# 05-03-2025 v3\n* [X] 9:00 {id:1} => gym\n",
+        )
+        .await
+        .unwrap();
+
+        let heatmap = Heatmap::collect(&db_dir, 2025, false).await.unwrap();
+        assert_eq!(heatmap.counts.len(), 365);
+        let count = |heatmap: &Heatmap, day| {
+            heatmap
+                .counts
+                .iter()
+                .find(|(date, _)| *date == Local.ymd(2025, 3, day))
+                .unwrap()
+                .1
+        };
+        assert_eq!(count(&heatmap, 4), 2);
+        assert_eq!(count(&heatmap, 5), 1);
+        assert_eq!(count(&heatmap, 6), 0);
+        assert_eq!(heatmap.level(2), 4);
+        assert_eq!(heatmap.level(1), 2);
+        assert_eq!(heatmap.level(0), 0);
+
+        let heatmap = Heatmap::collect(&db_dir, 2025, true).await.unwrap();
+        assert_eq!(count(&heatmap, 4), 3);
+        assert_eq!(count(&heatmap, 5), 0);
+
+        // Only the schedules which were there are kept.
+        assert_eq!(Schedule::stored_dates(&db_dir).await.unwrap().len(), 2);
+        assert!(Heatmap::collect(&db_dir, 2024, false)
+            .await
+            .unwrap()
+            .counts
+            .iter()
+            .all(|(_, count)| *count == 0));
+        assert!(!db_dir.join("2024").exists());
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+//! Git history of the database, enabled with `git = true` in the `[history]` config section.
+//!
+//! `db/` is a git repository, and every write of a schedule commits the file with a message
+//! describing the change, like `add 19-10-2026: write report`. Commands changing many schedules
+//! at once, like `import`, commit all of their changes together instead.
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::{Date, Datelike, Local};
+use tokio::fs;
+
+use crate::{config::HistoryConfig, error::Error};
+
+/// Database directories whose history is enabled.
+static ENABLED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// Set while a `batch` runs, so that the schedules it writes are not committed one by one.
+static HELD: AtomicBool = AtomicBool::new(false);
+
+/// Path of the schedule file of `date`, relative to the database directory.
+fn date_path(date: &Date<Local>) -> String {
+    format!("{}/{}/{}", date.year(), date.month(), date.day())
+}
+
+/// Runs git in `db_dir` with `args`, returning its output.
+fn git(db_dir: &Path, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(db_dir)
+        .args(args)
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Enables or disables the history of `db_dir` as set in `config`.
+pub fn init(db_dir: &Path, config: &HistoryConfig) {
+    let mut enabled = ENABLED.lock().unwrap();
+    let enabled = enabled.get_or_insert_with(HashSet::new);
+    if config.git {
+        enabled.insert(db_dir.to_path_buf());
+    } else {
+        enabled.remove(db_dir);
+    }
+}
+
+/// Whether the history of `db_dir` is enabled.
+pub fn enabled(db_dir: &Path) -> bool {
+    matches!(ENABLED.lock().unwrap().as_ref(), Some(enabled) if enabled.contains(db_dir))
+}
+
+/// Commits the changes to the schedules of `db_dir` with `message`, creating the repository if
+/// needed. Nothing is committed if nothing changed, or if history is not enabled.
+fn commit_now(db_dir: &Path, message: &str) -> Result<(), Error> {
+    if !enabled(db_dir) {
+        return Ok(());
+    }
+    if !db_dir.join(".git").exists() {
+        git(db_dir, &["init", "-q"])?;
+    }
+
+    git(db_dir, &["add", "-A", "."])?;
+    if git(db_dir, &["status", "--porcelain"])?.is_empty() {
+        return Ok(());
+    }
+
+    // Commits are made as timaru unless the user set up their own identity.
+    let identity: &[&str] = match git(db_dir, &["config", "user.email"]) {
+        Ok(_) => &[],
+        Err(_) => &[
+            "-c",
+            "user.name=timaru",
+            "-c",
+            "user.email=timaru@localhost",
+        ],
+    };
+    git(
+        db_dir,
+        &[identity, &["commit", "-q", "-m", message]].concat(),
+    )?;
+
+    Ok(())
+}
+
+/// Same as `commit_now`, for use by async code.
+pub async fn commit(db_dir: &Path, message: &str) -> Result<(), Error> {
+    commit_now(db_dir, message)
+}
+
+/// Commits the schedule file `file`, which was just written, with `message`, unless a `batch`
+/// is running.
+pub(crate) fn record(file: &Path, message: &str) -> Result<(), Error> {
+    // Schedule files are at `<db>/<year>/<month>/<day>`.
+    match file.ancestors().nth(3) {
+        Some(db_dir) if !HELD.load(Ordering::SeqCst) => commit_now(db_dir, message),
+        _ => Ok(()),
+    }
+}
+
+/// Runs `changes`, committing all the schedules it writes at once with `message`.
+pub async fn batch<T>(
+    db_dir: &Path,
+    message: &str,
+    changes: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    HELD.store(true, Ordering::SeqCst);
+    let res = changes.await;
+    HELD.store(false, Ordering::SeqCst);
+    commit(db_dir, message).await?;
+    res
+}
+
+/// The commits which changed the schedule of `date`, or any schedule, most recent first.
+pub async fn history(db_dir: &Path, date: Option<Date<Local>>) -> Result<String, Error> {
+    if fs::metadata(db_dir.join(".git")).await.is_err() {
+        return Err(Error::NoHistory);
+    }
+
+    let mut args = vec![
+        "log",
+        "--date=format:%d-%m-%Y %H:%M",
+        "--format=%h %ad %s",
+        "--",
+    ];
+    let path = date.as_ref().map(date_path);
+    args.push(path.as_deref().unwrap_or("."));
+    git(db_dir, &args)
+}
+
+/// Restores the schedule of `date`, or all the schedules, as they were at the revision `rev`,
+/// and commits the restored files. A schedule which did not exist at `rev` is removed.
+pub async fn restore(db_dir: &Path, rev: &str, date: Option<Date<Local>>) -> Result<(), Error> {
+    if fs::metadata(db_dir.join(".git")).await.is_err() {
+        return Err(Error::NoHistory);
+    }
+
+    // Fails early if `rev` is not a revision, before anything is changed.
+    git(
+        db_dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "-q",
+            &format!("{}^{{commit}}", rev),
+        ],
+    )
+    .map_err(|_| Error::Git(format!("unknown revision {}", rev)))?;
+
+    let source = format!("--source={}", rev);
+    let message = match &date {
+        Some(date) => {
+            let path = date_path(date);
+            let existed = git(db_dir, &["cat-file", "-e", &format!("{}:{}", rev, path)]).is_ok();
+            if existed {
+                git(
+                    db_dir,
+                    &["restore", &source, "--staged", "--worktree", "--", &path],
+                )?;
+            } else {
+                match fs::remove_file(db_dir.join(&path)).await {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                    _ => {}
+                }
+            }
+            format!(
+                "restore {}-{}-{} from {}",
+                date.day(),
+                date.month(),
+                date.year(),
+                rev
+            )
+        }
+        None => {
+            git(
+                db_dir,
+                &["restore", &source, "--staged", "--worktree", "--", "."],
+            )?;
+            format!("restore all schedules from {}", rev)
+        }
+    };
+
+    commit(db_dir, &message).await
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_history() {
+        use std::env;
+
+        use chrono::{Duration, Local, TimeZone};
+        use tokio::fs;
+
+        use super::{batch, history, init};
+        use crate::{
+            config::HistoryConfig,
+            schedule::Schedule,
+            task::{Task, TaskTime},
+        };
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-history-{}", std::process::id()));
+        fs::create_dir_all(&db_dir).await.unwrap();
+        init(&db_dir, &HistoryConfig { git: true });
+        let date = Local.ymd(2026, 10, 19);
+        let task = |description: &str| {
+            Task::new(
+                TaskTime::Precise {
+                    time: date.and_hms(9, 0, 0),
+                },
+                description.to_string(),
+            )
+        };
+
+        Schedule::open(&db_dir, date)
+            .await
+            .unwrap()
+            .add_task(task("write report"))
+            .unwrap();
+        drop(Schedule::read(&db_dir, date).await.unwrap());
+        Schedule::open(&db_dir, date).await.unwrap().remove_task(1);
+        batch(&db_dir, "import tasks", async {
+            let mut schedule = Schedule::open(&db_dir, date).await?;
+            schedule.add_task(task("review"))?;
+            schedule.add_task(task("deploy"))?;
+            drop(schedule);
+            Schedule::open(&db_dir, date + Duration::days(1))
+                .await?
+                .add_task(task("rest"))?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        // Lines are `<rev> <date> <time> <message>`.
+        let messages = |log: String| -> Vec<String> {
+            log.lines()
+                .map(|line| line.splitn(4, ' ').nth(3).unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            messages(history(&db_dir, None).await.unwrap()),
+            vec![
+                "import tasks",
+                "remove 19-10-2026: write report",
+                "add 19-10-2026: write report",
+            ]
+        );
+        assert_eq!(
+            messages(
+                history(&db_dir, Some(date + Duration::days(1)))
+                    .await
+                    .unwrap()
+            ),
+            vec!["import tasks"]
+        );
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -1,7 +1,21 @@
+pub mod backup;
 pub mod cli;
+pub mod complete;
+pub mod config;
+pub mod editor;
 pub mod error;
+pub mod export;
+pub mod format;
+pub mod fsck;
+pub mod heatmap;
+pub mod history;
 pub mod parser;
 pub mod schedule;
+pub mod serve;
 pub mod setup;
+pub mod shell;
+pub mod stats;
+pub mod storage;
+pub mod sync;
 pub mod task;
 pub mod tui;
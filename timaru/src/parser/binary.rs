@@ -0,0 +1,493 @@
+//! Binary format of schedules, a compact alternative to the text format.
+//!
+//! A binary schedule starts with `BINARY_MAGIC` and a byte holding the version of the format,
+//! followed by the date and the tasks of the schedule. Integers are stored as LEB128 varints
+//! (zigzag encoded if signed), strings and lists are prefixed with their length, dates are
+//! stored as days since the Common Era and times as seconds since the Unix epoch.
+
+use std::{convert::TryFrom, path::PathBuf, str::FromStr};
+
+use chrono::{Date, DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+
+use crate::{
+    error::Error,
+    schedule::Schedule,
+    task::{Deadline, Priority, Subtask, Task, TaskTime, TimeOfDay, WorkInterval, Zone},
+};
+
+pub const BINARY_MAGIC: &[u8] = b"TIMARU-BIN";
+/// Version of the binary format written, bumped whenever the layout changes. Version 2 adds the
+/// timezone of tasks, and version 3 the date on which they were finished and priorities after `C`.
+pub const BINARY_VERSION: u8 = 3;
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u64(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.bytes.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.bytes.push(n as u8);
+    }
+
+    fn i64(&mut self, n: i64) {
+        self.u64(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn strs(&mut self, strs: &[String]) {
+        self.u64(strs.len() as u64);
+        for s in strs {
+            self.str(s);
+        }
+    }
+
+    fn date(&mut self, date: &Date<Local>) {
+        self.i64(date.num_days_from_ce() as i64);
+    }
+
+    fn datetime(&mut self, time: &DateTime<Local>) {
+        self.i64(time.timestamp());
+    }
+
+    fn time_of_day(&mut self, time: &TimeOfDay) {
+        match time {
+            TimeOfDay::Morning => self.u8(0),
+            TimeOfDay::Noon => self.u8(1),
+            TimeOfDay::AfterNoon => self.u8(2),
+            TimeOfDay::Evening => self.u8(3),
+            TimeOfDay::Night => self.u8(4),
+            TimeOfDay::MidNight => self.u8(5),
+            TimeOfDay::Custom(name) => {
+                self.u8(6);
+                self.str(name);
+            }
+        }
+    }
+
+    fn task(&mut self, task: &Task) {
+        self.str(&task.id);
+        match &task.time {
+            TaskTime::Precise { time } => {
+                self.u8(0);
+                self.datetime(time);
+            }
+            TaskTime::General { time } => {
+                self.u8(1);
+                self.time_of_day(time);
+            }
+            TaskTime::Period { start, end } => {
+                self.u8(2);
+                self.datetime(start);
+                self.datetime(end);
+            }
+            TaskTime::GeneralPeriod { start, end } => {
+                self.u8(3);
+                self.time_of_day(start);
+                self.time_of_day(end);
+            }
+        }
+        self.str(&task.description);
+        self.bool(task.finished);
+
+        match task.pomodoro {
+            Some((total, done)) => {
+                self.u8(1);
+                self.u8(total);
+                self.u8(done);
+            }
+            None => self.u8(0),
+        }
+        self.u8(match task.priority {
+            None => 0,
+            Some(Priority::High) => 1,
+            Some(Priority::Medium) => 2,
+            Some(Priority::Low) => 3,
+            Some(Priority::Lower(n)) => 4 + n,
+        });
+        match &task.deadline {
+            None => self.u8(0),
+            Some(Deadline { date, time: None }) => {
+                self.u8(1);
+                self.date(date);
+            }
+            Some(Deadline {
+                date,
+                time: Some(time),
+            }) => {
+                self.u8(2);
+                self.date(date);
+                self.u64(time.num_seconds_from_midnight() as u64);
+            }
+        }
+        self.strs(&task.dependencies);
+
+        self.u64(task.subtasks.len() as u64);
+        for subtask in task.subtasks.iter() {
+            self.str(&subtask.description);
+            self.bool(subtask.finished);
+        }
+        self.u64(task.intervals.len() as u64);
+        for interval in task.intervals.iter() {
+            self.datetime(&interval.start);
+            match &interval.end {
+                Some(end) => {
+                    self.u8(1);
+                    self.datetime(end);
+                }
+                None => self.u8(0),
+            }
+        }
+        self.strs(&task.notes);
+
+        match &task.modified {
+            Some(modified) => {
+                self.u8(1);
+                self.datetime(modified);
+            }
+            None => self.u8(0),
+        }
+        match &task.tz {
+            Some(tz) => {
+                self.u8(1);
+                self.str(&tz.as_string());
+            }
+            None => self.u8(0),
+        }
+        match &task.done {
+            Some(done) => {
+                self.u8(1);
+                self.date(done);
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    /// Version of the format of the bytes.
+    version: u8,
+}
+
+const INVALID: Error = Error::Parse("invalid binary schedule");
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8, Error> {
+        let (&n, rest) = self.bytes.split_first().ok_or(INVALID)?;
+        self.bytes = rest;
+        Ok(n)
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut n = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(n);
+            }
+        }
+        Err(INVALID)
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        let n = self.u64()?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        match self.u64()? as usize {
+            // Every item takes at least a byte, so longer lists are invalid.
+            len if len <= self.bytes.len() => Ok(len),
+            _ => Err(INVALID),
+        }
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(INVALID),
+        }
+    }
+
+    fn str(&mut self) -> Result<String, Error> {
+        let len = self.len()?;
+        let (s, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        String::from_utf8(s.to_vec()).map_err(|_| INVALID)
+    }
+
+    fn strs(&mut self) -> Result<Vec<String>, Error> {
+        (0..self.len()?).map(|_| self.str()).collect()
+    }
+
+    fn date(&mut self) -> Result<Date<Local>, Error> {
+        let days = self.i64()?;
+        let days = i32::try_from(days).map_err(|_| INVALID)?;
+        let date = NaiveDate::from_num_days_from_ce_opt(days).ok_or(INVALID)?;
+        Local.from_local_date(&date).earliest().ok_or(INVALID)
+    }
+
+    fn datetime(&mut self) -> Result<DateTime<Local>, Error> {
+        Local.timestamp_opt(self.i64()?, 0).single().ok_or(INVALID)
+    }
+
+    fn time_of_day(&mut self) -> Result<TimeOfDay, Error> {
+        Ok(match self.u8()? {
+            0 => TimeOfDay::Morning,
+            1 => TimeOfDay::Noon,
+            2 => TimeOfDay::AfterNoon,
+            3 => TimeOfDay::Evening,
+            4 => TimeOfDay::Night,
+            5 => TimeOfDay::MidNight,
+            6 => TimeOfDay::Custom(self.str()?),
+            _ => return Err(INVALID),
+        })
+    }
+
+    fn task(&mut self) -> Result<Task, Error> {
+        let id = self.str()?;
+        let time = match self.u8()? {
+            0 => TaskTime::Precise {
+                time: self.datetime()?,
+            },
+            1 => TaskTime::General {
+                time: self.time_of_day()?,
+            },
+            2 => TaskTime::Period {
+                start: self.datetime()?,
+                end: self.datetime()?,
+            },
+            3 => TaskTime::GeneralPeriod {
+                start: self.time_of_day()?,
+                end: self.time_of_day()?,
+            },
+            _ => return Err(INVALID),
+        };
+        let description = self.str()?;
+        let finished = self.bool()?;
+
+        let pomodoro = match self.bool()? {
+            true => Some((self.u8()?, self.u8()?)),
+            false => None,
+        };
+        let priority = match self.u8()? {
+            0 => None,
+            1 => Some(Priority::High),
+            2 => Some(Priority::Medium),
+            3 => Some(Priority::Low),
+            n @ 4..=26 if self.version >= 3 => Some(Priority::Lower(n - 4)),
+            _ => return Err(INVALID),
+        };
+        let deadline = match self.u8()? {
+            0 => None,
+            1 => Some(Deadline {
+                date: self.date()?,
+                time: None,
+            }),
+            2 => Some(Deadline {
+                date: self.date()?,
+                time: Some(
+                    NaiveTime::from_num_seconds_from_midnight_opt(self.u64()? as u32, 0)
+                        .ok_or(INVALID)?,
+                ),
+            }),
+            _ => return Err(INVALID),
+        };
+        let dependencies = self.strs()?;
+
+        let subtasks = (0..self.len()?)
+            .map(|_| {
+                Ok(Subtask {
+                    description: self.str()?,
+                    finished: self.bool()?,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        let intervals = (0..self.len()?)
+            .map(|_| {
+                Ok(WorkInterval {
+                    start: self.datetime()?,
+                    end: match self.bool()? {
+                        true => Some(self.datetime()?),
+                        false => None,
+                    },
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        let notes = self.strs()?;
+
+        let modified = match self.bool()? {
+            true => Some(self.datetime()?),
+            false => None,
+        };
+        let tz = match self.version >= 2 && self.bool()? {
+            true => Some(Zone::from_str(&self.str()?).map_err(|_| INVALID)?),
+            false => None,
+        };
+        let done = match self.version >= 3 && self.bool()? {
+            true => Some(self.date()?),
+            false => None,
+        };
+
+        Ok(Task {
+            id,
+            time,
+            description,
+            pomodoro,
+            priority,
+            deadline,
+            dependencies,
+            subtasks,
+            intervals,
+            notes,
+            finished,
+            done,
+            tz,
+            modified,
+        })
+    }
+}
+
+impl Schedule {
+    /// The schedule in the binary format, its tasks being stored in the order of listings.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(BINARY_MAGIC);
+        writer.u8(BINARY_VERSION);
+
+        writer.date(&self.date);
+        let tasks = self.sorted_tasks();
+        writer.u64(tasks.len() as u64);
+        for (_, task) in tasks {
+            writer.task(task);
+        }
+
+        writer.bytes
+    }
+
+    /// Parses a schedule in the binary format, read from `file`.
+    pub fn from_bytes(file: PathBuf, input: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            bytes: input.strip_prefix(BINARY_MAGIC).ok_or(INVALID)?,
+            version: 0,
+        };
+        reader.version = match reader.u8()? {
+            0 => return Err(INVALID),
+            version if version > BINARY_VERSION => return Err(Error::NewerVersion(version as u32)),
+            version => version,
+        };
+
+        let date = reader.date()?;
+        // Tasks are numbered from 1 by a `u8`, so a schedule holds at most 255 of them.
+        let tasks = (0..reader.len()?)
+            .map(|idx| {
+                let idx = u8::try_from(idx + 1).map_err(|_| INVALID)?;
+                Ok((idx, reader.task()?))
+            })
+            .collect::<Result<std::collections::HashMap<_, _>, Error>>()?;
+        let loaded = tasks
+            .values()
+            .map(|task| (task.id.clone(), task.as_string()))
+            .collect();
+
+        Ok(Self {
+            date,
+            tasks,
+            file,
+            loaded,
+            read_only: false,
+            stored: None,
+        })
+    }
+}
+
+mod test {
+    #[test]
+    fn test_binary_round_trip() {
+        use std::path::PathBuf;
+
+        use crate::schedule::Schedule;
+
+        let input = "# 19-10-2026
+* [ ] 9:00 - 10:30 (4, 1) {id:1a2b3c4d priority:A due:20-10-2026@17:00} => write report
+    - [X] outline
+    - [ ] draft
+    @ 2026-10-19T09:05:00+00:00 - 2026-10-19T10:45:00+00:00
+    > first line
+    >
+* [X] morning {id:5e6f7a8b priority:F after:1a2b3c4d done:19-10-2026 modified:2026-10-19T11:00:00+00:00} => review
+* [ ] gym - 18:00:00 {id:9c0d1e2f due:25-10-2026} => stretch
+* [ ] 8:30 {id:3a4b5c6d tz:America/New_York} => call";
+
+        // Schedules read from strings are not written back to their file when dropped.
+        let mut schedule = Schedule::from_str(PathBuf::from("19"), input).unwrap();
+        schedule.read_only = true;
+        let bytes = schedule.as_bytes();
+        let mut parsed = Schedule::from_bytes(PathBuf::from("19"), &bytes).unwrap();
+        parsed.read_only = true;
+
+        assert!(bytes.len() < input.len());
+        assert_eq!(parsed.as_string(), schedule.as_string());
+        assert!(Schedule::from_bytes(PathBuf::from("19"), &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_binary_out_of_range() {
+        use std::path::PathBuf;
+
+        use super::{Writer, BINARY_MAGIC, BINARY_VERSION};
+        use crate::schedule::Schedule;
+
+        let header = |days: i64, len: u64| {
+            let mut writer = Writer::default();
+            writer.bytes.extend_from_slice(BINARY_MAGIC);
+            writer.u8(BINARY_VERSION);
+            writer.i64(days);
+            writer.u64(len);
+            writer.bytes
+        };
+        let mut schedule = Schedule::from_str(
+            PathBuf::from("19"),
+            "# 19-10-2026\n* [ ] 9:00 {id:1a2b3c4d} => write report",
+        )
+        .unwrap();
+        schedule.read_only = true;
+        let bytes = schedule.as_bytes();
+        let days = 739_543;
+        let task = &bytes[header(days, 1).len()..];
+        let with_tasks = |days: i64, count: usize| {
+            let mut bytes = header(days, count as u64);
+            for _ in 0..count {
+                bytes.extend_from_slice(task);
+            }
+            Schedule::from_bytes(PathBuf::from("19"), &bytes).map(|mut schedule| {
+                schedule.read_only = true;
+                schedule
+            })
+        };
+
+        assert_eq!(with_tasks(days, 255).unwrap().tasks.len(), 255);
+        // Tasks are numbered by a `u8`, and dates are days of an `i32`.
+        assert!(with_tasks(days, 256).is_err());
+        assert!(with_tasks(i32::MAX as i64 + 1, 1).is_err());
+    }
+}
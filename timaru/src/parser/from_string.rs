@@ -1,9 +1,9 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
-use chrono::{Date, DateTime, Local, LocalResult, TimeZone};
+use chrono::{Date, DateTime, FixedOffset, Local, LocalResult, NaiveTime, TimeZone};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until, take_while},
+    bytes::complete::{tag, take_till, take_until, take_while},
     character::{
         complete::{char, digit1, space0, space1},
         is_newline, is_space,
@@ -16,8 +16,9 @@ use nom::{
 use crate::{
     change_parse_err,
     error::Error,
+    parser::migrate,
     schedule::Schedule,
-    task::{Task, TaskTime, TimeOfDay},
+    task::{Deadline, Priority, Subtask, Task, TaskTime, TimeOfDay, WorkInterval, Zone},
 };
 
 #[inline]
@@ -52,7 +53,10 @@ pub fn get_ymd(input: &str) -> IResult<&str, (u32, u32, i32)> {
 #[inline]
 pub fn get_date(input: &str) -> Result<Date<Local>, Error> {
     let (d, m, y) = change_parse_err!(get_ymd(input).map(|(_, date)| date), "wrong date format");
-    Ok(Local.ymd(y, m, d))
+    Local
+        .ymd_opt(y, m, d)
+        .earliest()
+        .ok_or(Error::Parse("date can not exist"))
 }
 
 #[inline]
@@ -61,25 +65,39 @@ fn clear_ws(input: &str) -> IResult<&str, &str> {
 }
 
 pub fn parse_time(input: &str, date: &Date<Local>) -> Result<DateTime<Local>, Error> {
+    parse_time_in(input, date, None)
+}
+
+/// Same as `parse_time`, for a time on the clocks of `zone`, or the local ones if none is given.
+pub fn parse_time_in(
+    input: &str,
+    date: &Date<Local>,
+    zone: Option<&Zone>,
+) -> Result<DateTime<Local>, Error> {
     let s: Vec<&str> = input.split(':').collect();
-    match s.len() {
+    let time = match s.len() {
         1 => match s[0].parse::<u32>() {
-            Ok(hour) => Ok(date.and_hms(hour, 0, 0)),
-            _ => Err(Error::Parse("time")),
+            Ok(hour) => NaiveTime::from_hms_opt(hour, 0, 0),
+            _ => None,
         },
         2 => match (s[0].parse::<u32>(), s[1].parse::<u32>()) {
-            (Ok(hour), Ok(min)) => Ok(date.and_hms(hour, min, 0)),
-            _ => Err(Error::Parse("time")),
+            (Ok(hour), Ok(min)) => NaiveTime::from_hms_opt(hour, min, 0),
+            _ => None,
         },
         3 => match (
             s[0].parse::<u32>(),
             s[1].parse::<u32>(),
             s[2].parse::<u32>(),
         ) {
-            (Ok(hour), Ok(min), Ok(sec)) => Ok(date.and_hms(hour, min, sec)),
-            _ => Err(Error::Parse("time")),
+            (Ok(hour), Ok(min), Ok(sec)) => NaiveTime::from_hms_opt(hour, min, sec),
+            _ => None,
         },
-        _ => Err(Error::Parse("time")),
+        _ => None,
+    };
+
+    match time {
+        Some(time) => Zone::at_wall_clock_of(zone, &date.naive_local().and_time(time)),
+        None => Err(Error::Parse("time")),
     }
 }
 
@@ -96,15 +114,64 @@ impl Schedule {
             LocalResult::Ambiguous(date, _) => date,
         };
 
-        let mut tasks = HashMap::with_capacity(5);
+        // Files written before versions were introduced have no version in their header.
+        let (input, version) = match tuple::<&str, _, nom::error::Error<&str>, _>((
+            space1,
+            char('v'),
+            map_res(digit1, |s: &str| s.parse::<u32>()),
+        ))(input)
+        {
+            Ok((input, (_, _, version))) => (input, version),
+            Err(_) => (input, 1),
+        };
+
+        let mut tasks: HashMap<u8, Task> = HashMap::with_capacity(5);
 
         let (input, _) = change_parse_err!(clear_ws(input), "whitespace before tasks");
+        let input = migrate(version, &date, input)?;
+
+        for line in input.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Indented lines belong to the task above them, and are either notes or subtasks.
+            if line.starts_with(char::is_whitespace) {
+                let task = match tasks.get_mut(&(tasks.len() as u8)) {
+                    Some(task) => task,
+                    None => return Err(Error::Parse("indented line without a task")),
+                };
 
-        for (idx, line) in input.lines().enumerate() {
-            tasks.insert((idx as u8) + 1, Task::from_str(line, &date)?);
+                let line_trimmed = line.trim_start();
+                if let Some(note) = line_trimmed.strip_prefix('>') {
+                    task.notes
+                        .push(note.strip_prefix(' ').unwrap_or(note).to_string());
+                } else if line_trimmed.starts_with('@') {
+                    task.intervals.push(WorkInterval::from_str(line_trimmed)?);
+                } else {
+                    task.subtasks.push(Subtask::from_str(line)?);
+                }
+            } else {
+                tasks.insert(tasks.len() as u8 + 1, Task::from_str(line, &date)?);
+            }
         }
 
-        Ok(Self { date, tasks, file })
+        for task in tasks.values_mut() {
+            task.derive_finished();
+        }
+        let loaded = tasks
+            .values()
+            .map(|task| (task.id.clone(), task.as_string()))
+            .collect();
+
+        Ok(Self {
+            date,
+            tasks,
+            file,
+            loaded,
+            read_only: false,
+            stored: None,
+        })
     }
 }
 
@@ -131,20 +198,17 @@ impl Task {
                 return Err(Error::Parse("finished marking of task"));
             };
 
-        let (input, (_, _, time_str, _)) = change_parse_err!(
+        let (mut input, (_, _, time_str)) = change_parse_err!(
             tuple::<&str, _, nom::error::Error<&str>, _>((
                 char(']'),
                 space1,
-                alt((take_until("("), take_until("=>"))),
-                space0,
+                take_till(|c| c == '(' || c == '{' || c == '='),
             ))(input),
             "task time"
         );
 
-        let time = TaskTime::from_str(time_str.trim(), date)?;
-
-        let (description, pomodoro) = if input.starts_with('(') {
-            let (input, (_, _, times, _, _, _, done, _, _, _, _, _)) = change_parse_err!(
+        let pomodoro = if input.starts_with('(') {
+            let (input_left, (_, _, times, _, _, _, done, _, _, _)) = change_parse_err!(
                 tuple::<&str, _, nom::error::Error<&str>, _>((
                     char('('),
                     space0,
@@ -156,44 +220,129 @@ impl Task {
                     space0,
                     char(')'),
                     space0,
-                    tag("=>"),
-                    space0,
                 ))(input),
-                "pomodoro and/or description"
+                "pomodoro"
             );
+            input = input_left;
             match (times.parse::<u8>(), done.parse::<u8>()) {
-                (Ok(times), Ok(done)) => (input, Some((times, done))),
-                _ => return Err(Error::Parse("pomodoro and/or description")),
+                (Ok(times), Ok(done)) => Some((times, done)),
+                _ => return Err(Error::Parse("pomodoro")),
             }
         } else {
-            (
-                change_parse_err!(
-                    tuple::<&str, _, nom::error::Error<&str>, _>((space0, tag("=>"), space0))(
-                        input
-                    ),
-                    "description"
-                )
-                .0,
-                None,
-            )
+            None
         };
 
+        let mut id = None;
+        let mut priority = None;
+        let mut deadline = None;
+        let mut dependencies = Vec::new();
+        let mut modified = None;
+        let mut done = None;
+        let mut tz = None;
+
+        if input.starts_with('{') {
+            let (input_left, (_, attrs, _, _)) = change_parse_err!(
+                tuple::<&str, _, nom::error::Error<&str>, _>((
+                    char('{'),
+                    take_until("}"),
+                    char('}'),
+                    space0,
+                ))(input),
+                "task attributes"
+            );
+            input = input_left;
+
+            for attr in attrs.split_whitespace() {
+                let (key, value) = match attr.split_once(':') {
+                    Some(key_value) => key_value,
+                    None => return Err(Error::Parse("task attribute")),
+                };
+                match key {
+                    "id" => id = Some(value.to_string()),
+                    "after" => dependencies = value.split(',').map(str::to_string).collect(),
+                    "priority" => priority = Some(Priority::from_str(value)?),
+                    "due" => deadline = Some(Deadline::from_str(value)?),
+                    "tz" => tz = Some(Zone::from_str(value)?),
+                    "done" => done = Some(get_date(value)?),
+                    "modified" => {
+                        let time = change_parse_err!(
+                            DateTime::parse_from_rfc3339(value),
+                            "modification time"
+                        );
+                        modified = Some(time.with_timezone(&Local));
+                    }
+                    _ => return Err(Error::Parse("unknown task attribute")),
+                }
+            }
+        }
+
+        let (description, _) = change_parse_err!(
+            tuple::<&str, _, nom::error::Error<&str>, _>((space0, tag("=>"), space0))(input),
+            "description"
+        );
+        let time = TaskTime::from_str_in(time_str.trim(), date, tz.as_ref())?;
+
         Ok(Self {
+            // Tasks from before ids were introduced get one the first time they are read.
+            id: id.unwrap_or_else(Task::new_id),
             time,
             pomodoro,
+            priority,
+            deadline,
+            dependencies,
             description: description.to_string(),
+            subtasks: Vec::new(),
+            intervals: Vec::new(),
+            notes: Vec::new(),
             finished,
+            done,
+            tz,
+            modified,
+        })
+    }
+}
+
+impl FromStr for Subtask {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let (input, (_, _, _, _, finished, _, _)) = change_parse_err!(
+            tuple::<&str, _, nom::error::Error<&str>, _>((
+                space1,
+                alt((char('-'), char('*'))),
+                space0,
+                char('['),
+                alt((char('X'), char(' '))),
+                char(']'),
+                space0,
+            ))(input),
+            "subtask"
+        );
+
+        Ok(Self {
+            description: input.to_string(),
+            finished: finished == 'X',
         })
     }
 }
 
 impl TaskTime {
     pub fn from_str(input: &str, date: &Date<Local>) -> Result<TaskTime, Error> {
+        TaskTime::from_str_in(input, date, None)
+    }
+
+    /// Same as `from_str`, for times on the clocks of `zone`, or the local ones if none is given.
+    pub fn from_str_in(
+        input: &str,
+        date: &Date<Local>,
+        zone: Option<&Zone>,
+    ) -> Result<TaskTime, Error> {
+        let parse_time = |input| parse_time_in(input, date, zone);
         let s: Vec<&str> = input.split('-').map(|s| s.trim()).collect();
 
         match s.len() {
             1 => {
-                if let Ok(time) = parse_time(s[0], date) {
+                if let Ok(time) = parse_time(s[0]) {
                     Ok(TaskTime::Precise { time })
                 } else {
                     Ok(TaskTime::General {
@@ -203,8 +352,8 @@ impl TaskTime {
             }
 
             2 => {
-                if let Ok(start) = parse_time(s[0], date) {
-                    parse_time(s[1], date).map(|end| TaskTime::Period { start, end })
+                if let Ok(start) = parse_time(s[0]) {
+                    parse_time(s[1]).map(|end| TaskTime::Period { start, end })
                 } else {
                     Ok(TaskTime::GeneralPeriod {
                         start: TimeOfDay::from(s[0]),
@@ -217,6 +366,100 @@ impl TaskTime {
     }
 }
 
+impl FromStr for WorkInterval {
+    type Err = Error;
+
+    /// Parses an interval of the form `@ <start> - [end]`, both being RFC 3339 timestamps.
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let parse = |time: &str| match DateTime::parse_from_rfc3339(time.trim()) {
+            Ok(time) => Ok(time.with_timezone(&Local)),
+            Err(_) => Err(Error::Parse("work interval time")),
+        };
+
+        let input = match input.trim().strip_prefix('@') {
+            Some(input) => input.trim(),
+            None => return Err(Error::Parse("work interval")),
+        };
+        let (start, end) = match input.strip_suffix('-') {
+            Some(start) => (start, None),
+            None => match input.split_once(" - ") {
+                Some((start, end)) => (start, Some(parse(end)?)),
+                None => return Err(Error::Parse("work interval")),
+            },
+        };
+
+        Ok(Self {
+            start: parse(start)?,
+            end,
+        })
+    }
+}
+
+impl FromStr for Deadline {
+    type Err = Error;
+
+    /// Parses a deadline of the form `dd-mm-yyyy`, optionally followed by `@` and a time.
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let mut parts = input.splitn(2, '@');
+        let date = get_date(parts.next().unwrap_or_default())?;
+        let time = match parts.next() {
+            Some(time) => Some(parse_time(time, &date)?.time()),
+            None => None,
+        };
+
+        Ok(Deadline { date, time })
+    }
+}
+
+impl FromStr for Zone {
+    type Err = Error;
+
+    /// Parses an IANA timezone like `Europe/Paris`, or an offset from UTC like `+02:00` or `-5`.
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let sign = match input.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => {
+                return input
+                    .parse()
+                    .map(Zone::Named)
+                    .map_err(|_| Error::Parse("timezone"))
+            }
+        };
+
+        let mut parts = input[1..].splitn(2, ':');
+        let hours = parts.next().and_then(|hours| hours.parse::<i32>().ok());
+        let minutes = parts
+            .next()
+            .map_or(Some(0), |minutes| minutes.parse::<i32>().ok());
+        match (hours, minutes) {
+            (Some(hours @ 0..=23), Some(minutes @ 0..=59)) => {
+                FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+                    .map(Zone::Offset)
+                    .ok_or(Error::Parse("timezone offset"))
+            }
+            _ => Err(Error::Parse("timezone offset")),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = Error;
+
+    #[rustfmt::skip]
+    fn from_str(input: &str) -> Result<Self, Error> {
+        match input.to_lowercase().as_str() {
+            "a" | "1" | "high"   => Ok(Priority::High),
+            "b" | "2" | "medium" => Ok(Priority::Medium),
+            "c" | "3" | "low"    => Ok(Priority::Low),
+            lower if lower.len() == 1 && ("d"..="z").contains(&lower) => {
+                Ok(Priority::Lower(lower.as_bytes()[0] - b'd'))
+            }
+            _                    => Err(Error::Parse("priority")),
+        }
+    }
+}
+
 impl From<&str> for TimeOfDay {
     #[rustfmt::skip]
     fn from(input: &str) -> Self {
@@ -248,7 +491,130 @@ mod test {
 - [X] 5:30 (1, 1) => do some other stuff
 "#;
 
-        let schedule = Schedule::from_str(PathBuf::from(""), schedule_str).unwrap();
+        let mut schedule = Schedule::from_str(PathBuf::from(""), schedule_str).unwrap();
+        // The schedule has no file to be written to.
+        schedule.read_only = true;
         println!("{:?}", schedule);
     }
+
+    #[test]
+    fn test_task_priority() {
+        use super::{Priority, Task};
+        use chrono::Local;
+
+        let task = Task::from_str(
+            "* [ ] morning {id:1 priority:A} => urgent stuff",
+            &Local::today(),
+        )
+        .unwrap();
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.description, "urgent stuff");
+        assert_eq!(
+            task.as_string(),
+            "* [ ] morning {id:1 priority:A} => urgent stuff"
+        );
+    }
+
+    #[test]
+    fn test_task_subtasks() {
+        use std::str::FromStr;
+
+        use super::{Subtask, Task};
+        use chrono::Local;
+
+        let mut task = Task::from_str("* [ ] 10:00 {id:1} => release", &Local::today()).unwrap();
+        task.subtasks
+            .push(Subtask::from_str("    - [X] tag").unwrap());
+        task.subtasks
+            .push(Subtask::from_str("    - [ ] build").unwrap());
+        task.derive_finished();
+        assert!(!task.finished);
+        assert_eq!(
+            task.as_string(),
+            "* [ ] 10:00 {id:1} => release\n    - [X] tag\n    - [ ] build"
+        );
+
+        task.subtasks[1].finished = true;
+        task.derive_finished();
+        assert!(task.finished);
+    }
+
+    #[test]
+    fn test_task_notes() {
+        use super::Schedule;
+        use std::env;
+
+        let schedule_str = r#"
+# 12-12-2012 v3
+* [X] 14:00 {id:1} => meeting
+    - [X] agenda
+    > some notes
+    >
+    > https://example.com
+"#;
+
+        let file = env::temp_dir().join("timaru-test-task-notes");
+        let schedule = Schedule::from_str(file, schedule_str).unwrap();
+        let task = &schedule.tasks[&1];
+        assert_eq!(task.subtasks.len(), 1);
+        assert_eq!(task.notes, vec!["some notes", "", "https://example.com"]);
+        assert!(task.matches("EXAMPLE"));
+        assert_eq!(schedule.as_string().trim(), schedule_str.trim());
+    }
+
+    #[test]
+    fn test_task_deadline() {
+        use super::Task;
+        use chrono::{Local, NaiveTime, TimeZone};
+
+        let input = "* [ ] evening {id:1 priority:B due:20-10-2026@17:30} => submit report";
+        let task = Task::from_str(input, &Local::today()).unwrap();
+        let deadline = task.deadline.as_ref().unwrap();
+        assert_eq!(deadline.date, Local.ymd(2026, 10, 20));
+        assert_eq!(deadline.time, Some(NaiveTime::from_hms(17, 30, 0)));
+        assert_eq!(task.as_string(), input);
+
+        let now = Local.ymd(2026, 10, 18).and_hms(17, 30, 0);
+        assert_eq!(deadline.countdown(&now), "due in 2 days");
+        let now = Local.ymd(2026, 10, 20).and_hms(20, 0, 0);
+        assert_eq!(deadline.countdown(&now), "overdue by 2 hours");
+
+        let input = "* [ ] evening {id:1 due:20-10-2026@17:30:15} => submit report";
+        let task = Task::from_str(input, &Local::today()).unwrap();
+        assert_eq!(task.as_string(), input);
+
+        let input = "* [ ] evening {id:1 due:31-2-2026} => submit report";
+        assert!(Task::from_str(input, &Local::today()).is_err());
+    }
+
+    #[test]
+    fn test_zone() {
+        use std::str::FromStr;
+
+        use super::Zone;
+        use chrono::FixedOffset;
+
+        assert_eq!(
+            Zone::from_str("Europe/Paris").unwrap(),
+            Zone::Named(chrono_tz::Europe::Paris)
+        );
+        assert_eq!(
+            Zone::from_str("+05:30").unwrap(),
+            Zone::Offset(FixedOffset::east(5 * 3600 + 30 * 60))
+        );
+        assert_eq!(
+            Zone::from_str("-5").unwrap(),
+            Zone::Offset(FixedOffset::west(5 * 3600))
+        );
+        assert_eq!(Zone::from_str("+05:30").unwrap().as_string(), "+05:30");
+        assert_eq!(Zone::from_str("-5").unwrap().as_string(), "-05:00");
+        assert_eq!(
+            Zone::from_str("America/New_York").unwrap().as_string(),
+            "America/New_York"
+        );
+
+        for input in ["Mars/Olympus", "+24", "+5:60", "+", "-ab", ""] {
+            assert!(Zone::from_str(input).is_err(), "{}", input);
+        }
+    }
 }
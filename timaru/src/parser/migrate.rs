@@ -0,0 +1,173 @@
+//! Versions of the text format of schedules, and migrations between them.
+//!
+//! The header of a schedule file holds the version of its format, as in `# 19-10-2026 v3`. Files
+//! without one are of version 1, which covers every layout written before versions were
+//! introduced, since each of them only added optional parts to tasks. A schedule of an older
+//! version is upgraded by the migrations when it is read, and written in the current version.
+
+use chrono::{Date, Local};
+
+use crate::error::Error;
+
+/// Version of the text format written. Version 3 adds the `tz` attribute of tasks.
+pub const TEXT_VERSION: u32 = 3;
+
+/// Upgrades the tasks of the schedule of a date from a version to the next one.
+type Migration = fn(&Date<Local>, &str) -> Result<String, Error>;
+
+/// Migrations of the tasks of a schedule, the one at each index upgrading them from the version
+/// one above it.
+const MIGRATIONS: &[Migration] = &[v1_to_v2, v2_to_v3];
+
+/// Upgrades `tasks`, the lines of the schedule of `date` following its header, from `version` to
+/// `TEXT_VERSION`.
+pub fn migrate(version: u32, date: &Date<Local>, tasks: &str) -> Result<String, Error> {
+    if version > TEXT_VERSION {
+        return Err(Error::NewerVersion(version));
+    }
+    if version == 0 {
+        return Err(Error::Parse("schedule version"));
+    }
+
+    MIGRATIONS[version as usize - 1..]
+        .iter()
+        .try_fold(tasks.to_string(), |tasks, migration| {
+            migration(date, &tasks)
+        })
+}
+
+/// Id of the task on the `index`th line of the schedule of `date`. It only depends on the date
+/// and the line, so that a schedule which is read but not written yet, e.g. by listings, gives
+/// the same ids to its tasks every time it is read.
+fn legacy_id(date: &Date<Local>, index: usize, line: &str) -> String {
+    // FNV-1a, as the hashers of the standard library may change between versions of Rust.
+    let hash = format!("{} {} {}", date.format("%Y-%m-%d"), index, line)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:08x}", (hash ^ hash >> 32) as u32)
+}
+
+/// Gives an id to the tasks which have none, so that it is kept in the file instead of being
+/// generated anew whenever the schedule is read.
+fn v1_to_v2(date: &Date<Local>, tasks: &str) -> Result<String, Error> {
+    let mut migrated = String::with_capacity(tasks.len());
+
+    for (index, line) in tasks.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+            migrated += line;
+        } else {
+            let (head, description) = line.split_once("=>").ok_or(Error::Parse("description"))?;
+            match head.find('{') {
+                Some(start) if head[start..].contains("id:") => migrated += line,
+                Some(start) => {
+                    migrated += &format!(
+                        "{}id:{} {}=>{}",
+                        &head[..=start],
+                        legacy_id(date, index, line),
+                        &head[start + 1..],
+                        description
+                    );
+                }
+                None => {
+                    migrated += &format!(
+                        "{} {{id:{}}} =>{}",
+                        head.trim_end(),
+                        legacy_id(date, index, line),
+                        description
+                    )
+                }
+            }
+        }
+        migrated.push('\n');
+    }
+
+    Ok(migrated)
+}
+
+/// Files of version 2 are read as they are, version 3 only adding an optional attribute which
+/// older versions of timaru would not read.
+fn v2_to_v3(_: &Date<Local>, tasks: &str) -> Result<String, Error> {
+    Ok(tasks.to_string())
+}
+
+mod test {
+    #[test]
+    fn test_fixtures_parsing() {
+        use std::env;
+
+        use crate::schedule::Schedule;
+
+        /// Schedules as written by every version of timaru, along with the schedule they should be
+        /// read as.
+        const FIXTURES: &[(&str, &str)] = &[
+            (
+                include_str!("fixtures/v1-pomodoro.txt"),
+                "# 12-12-2012 v3
+* [ ] 4:30 (1, 0) {id:b1fb945a} => do some stuff
+* [X] 5:30 (1, 1) {id:9c63c710} => do some other stuff
+* [ ] morning - noon {id:2d3dc569} => read",
+            ),
+            (
+                include_str!("fixtures/v1-priority.txt"),
+                "# 12-12-2012 v3
+* [ ] morning {id:e4e34ae1 priority:A} => urgent stuff
+* [ ] 10:00 {id:eeee46ed} => meeting",
+            ),
+            (
+                include_str!("fixtures/v1-subtasks.txt"),
+                "# 12-12-2012 v3
+* [ ] 10:00 (2, 1) {id:06485c1e} => release
+    - [X] tag
+    - [ ] build
+    > check the changelog
+    >
+    > https://example.com",
+            ),
+            (
+                include_str!("fixtures/v1-deadline.txt"),
+                "# 12-12-2012 v3
+* [ ] evening {id:15920fd4 priority:B due:20-12-2012@17:30} => submit report",
+            ),
+            (
+                include_str!("fixtures/v1-ids.txt"),
+                "# 12-12-2012 v3
+* [X] 9:00 {id:1a2b3c4d} => write draft
+* [ ] 14:00 {id:5e6f7a8b due:14-12-2012 after:1a2b3c4d} => review draft
+    @ 2012-12-12T14:00:00+00:00 - 2012-12-12T14:30:00+00:00
+    @ 2012-12-12T15:00:00+00:00 -",
+            ),
+            (
+                include_str!("fixtures/v1-modified.txt"),
+                "# 12-12-2012 v3
+* [ ] night {id:9c0d1e2f modified:2012-12-12T20:00:00+00:00} => sleep early",
+            ),
+            (
+                include_str!("fixtures/v3-tz.txt"),
+                "# 12-12-2012 v3
+* [ ] 9:15 - 10:30 {id:4d5e6f7a tz:America/New_York} => call
+* [ ] 23:30 {id:8b9c0d1e tz:+05:30} => standup",
+            ),
+            (
+                include_str!("fixtures/v3-tz-hour.txt"),
+                "# 12-12-2012 v3
+* [ ] 9:00 - 10:30 {id:5e6f7a8b tz:America/New_York} => call",
+            ),
+            (
+                include_str!("fixtures/v2.txt"),
+                "# 12-12-2012 v3
+* [ ] 8:00 {id:3c4d5e6f priority:C} => stretch",
+            ),
+        ];
+
+        for (fixture, expected) in FIXTURES {
+            let file = env::temp_dir().join("timaru-test-fixtures-parsing");
+            let mut schedule = Schedule::from_str(file, fixture).unwrap();
+            schedule.read_only = true;
+            // Ids given by migrations only depend on the date and the line of the task, so they are
+            // the same however many times the schedule is read.
+            assert_eq!(schedule.as_string().trim(), *expected);
+        }
+    }
+}
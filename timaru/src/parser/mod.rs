@@ -1,6 +1,10 @@
+mod binary;
 #[macro_use]
 mod from_string;
+mod migrate;
 mod to_string;
 
+pub use binary::*;
 pub use from_string::*;
+pub use migrate::*;
 pub use to_string::*;
@@ -1,20 +1,43 @@
-use chrono::{Datelike, Timelike};
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
 
 use crate::{
+    format::{self, Formats},
+    parser::TEXT_VERSION,
     schedule::Schedule,
-    task::{Task, TaskTime, TimeOfDay},
+    task::{Deadline, Priority, Subtask, Task, TaskTime, TimeOfDay, WorkInterval, Zone},
 };
 
+/// How the indented lines belonging to a task are written by `Task::children_as_string_with`,
+/// which lets the exports lay them out like schedule files.
+pub struct ChildrenLayout {
+    /// Indentation of each line.
+    pub indent: &'static str,
+    /// Marker starting the lines of notes, which makes them read as plain text.
+    pub note: char,
+    /// Whether the work intervals are written.
+    pub intervals: bool,
+}
+
+impl ChildrenLayout {
+    /// The layout of schedule files.
+    pub const SCHEDULE: ChildrenLayout = ChildrenLayout {
+        indent: "    ",
+        note: '>',
+        intervals: true,
+    };
+}
+
 impl Schedule {
     pub fn as_string(&self) -> String {
         let mut s = format!(
-            "# {}-{}-{}\n",
+            "# {}-{}-{} v{}\n",
             self.date.day(),
             self.date.month(),
-            self.date.year()
+            self.date.year(),
+            TEXT_VERSION
         );
 
-        for task in self.tasks.values() {
+        for (_, task) in self.sorted_tasks() {
             s += &format!("{}\n", task.as_string());
         }
 
@@ -24,37 +47,117 @@ impl Schedule {
 
 impl Task {
     pub fn as_string(&self) -> String {
+        self.header_as_string() + &self.children_as_string()
+    }
+
+    /// The indented lines belonging to the task, i.e. its subtasks and notes, each preceded by a
+    /// newline.
+    pub fn children_as_string(&self) -> String {
+        self.children_as_string_with(&ChildrenLayout::SCHEDULE)
+    }
+
+    /// Same as `children_as_string`, with the lines laid out as in `layout`.
+    pub fn children_as_string_with(&self, layout: &ChildrenLayout) -> String {
+        let indent = layout.indent;
+        let mut s = String::new();
+        for subtask in self.subtasks.iter() {
+            s += &format!("\n{}{}", indent, subtask.as_string());
+        }
+        if layout.intervals {
+            for interval in self.intervals.iter() {
+                s += &format!("\n{}{}", indent, interval.as_string());
+            }
+        }
+        for line in self.notes.iter() {
+            if line.is_empty() {
+                s += &format!("\n{}{}", indent, layout.note);
+            } else {
+                s += &format!("\n{}{} {}", indent, layout.note, line);
+            }
+        }
+        s
+    }
+
+    /// The line of the task itself, without any of the indented lines belonging to it.
+    pub fn header_as_string(&self) -> String {
+        self.header_as_string_in(self.tz.as_ref())
+    }
+
+    /// Same as `header_as_string`, with the times of the task on the clocks of `zone`, or the
+    /// local ones if none is given.
+    pub fn header_as_string_in(&self, zone: Option<&Zone>) -> String {
+        self.header_as_string_with(zone, &Formats::default())
+    }
+
+    /// Same as `header_as_string_in`, with dates and times in `formats`.
+    pub fn header_as_string_with(&self, zone: Option<&Zone>, formats: &Formats) -> String {
         let mut s = format!(
             "* [{}] {} ",
             if self.finished { 'X' } else { ' ' },
-            self.time.as_string()
+            self.time.as_string_with(zone, formats)
         );
         if let Some((total, done)) = self.pomodoro {
             s += &format!("({}, {}) ", total, done);
         }
+
+        let mut attrs = vec![format!("id:{}", self.id)];
+        if let Some(priority) = self.priority {
+            attrs.push(format!("priority:{}", priority.as_str()));
+        }
+        if let Some(deadline) = &self.deadline {
+            attrs.push(format!("due:{}", deadline.as_string_with(formats)));
+        }
+        if !self.dependencies.is_empty() {
+            attrs.push(format!("after:{}", self.dependencies.join(",")));
+        }
+        if let Some(tz) = &self.tz {
+            attrs.push(format!("tz:{}", tz.as_string()));
+        }
+        if let Some(done) = &self.done {
+            attrs.push(format!("done:{}", formats.date(done.naive_local())));
+        }
+        if let Some(modified) = self.modified {
+            attrs.push(format!(
+                "modified:{}",
+                modified.format("%Y-%m-%dT%H:%M:%S%:z")
+            ));
+        }
+        s += &format!("{{{}}} ", attrs.join(" "));
+
         s += &format!("=> {}", self.description);
         s
     }
 }
 
+impl Subtask {
+    pub fn as_string(&self) -> String {
+        format!(
+            "- [{}] {}",
+            if self.finished { 'X' } else { ' ' },
+            self.description
+        )
+    }
+}
+
 impl TaskTime {
     pub fn as_string(&self) -> String {
+        self.as_string_in(None)
+    }
+
+    /// Same as `as_string`, with the times on the clocks of `zone`, or the local ones if none is
+    /// given.
+    pub fn as_string_in(&self, zone: Option<&Zone>) -> String {
+        self.as_string_with(zone, &Formats::default())
+    }
+
+    /// Same as `as_string_in`, with the times in the time format of `formats`.
+    pub fn as_string_with(&self, zone: Option<&Zone>, formats: &Formats) -> String {
+        let time = |time| formats.time(Zone::wall_clock_of(zone, time).time());
+
         match self {
-            TaskTime::Precise { time } => {
-                if time.minute() < 10 {
-                    format!("{}:0{}", time.hour(), time.minute())
-                } else {
-                    format!("{}:{}", time.hour(), time.minute())
-                }
-            }
+            TaskTime::Precise { time: precise } => time(precise),
             TaskTime::General { time } => time.as_str().to_string(),
-            TaskTime::Period { start, end } => format!(
-                "{}:{} - {}:{}",
-                start.hour(),
-                start.minute(),
-                end.hour(),
-                end.minute()
-            ),
+            TaskTime::Period { start, end } => format!("{} - {}", time(start), time(end)),
             TaskTime::GeneralPeriod { start, end } => {
                 format!("{} - {}", start.as_str(), end.as_str())
             }
@@ -62,6 +165,15 @@ impl TaskTime {
     }
 }
 
+impl Zone {
+    pub fn as_string(&self) -> String {
+        match self {
+            Zone::Named(tz) => tz.name().to_string(),
+            Zone::Offset(offset) => offset.to_string(),
+        }
+    }
+}
+
 impl TimeOfDay {
     pub fn as_str(&self) -> &str {
         match self {
@@ -75,3 +187,73 @@ impl TimeOfDay {
         }
     }
 }
+
+impl Priority {
+    pub fn as_str(&self) -> &str {
+        const LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        match self {
+            Priority::High => "A",
+            Priority::Medium => "B",
+            Priority::Low => "C",
+            Priority::Lower(n) => {
+                let idx = 3 + (*n as usize).min(22);
+                &LETTERS[idx..idx + 1]
+            }
+        }
+    }
+}
+
+impl WorkInterval {
+    pub fn as_string(&self) -> String {
+        const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+        match self.end {
+            Some(end) => format!("@ {} - {}", self.start.format(FORMAT), end.format(FORMAT)),
+            None => format!("@ {} -", self.start.format(FORMAT)),
+        }
+    }
+}
+
+/// Formats a duration as hours and minutes, e.g. `1h05m`.
+pub fn duration_as_string(duration: &Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}h{:02}m", minutes / 60, minutes % 60)
+}
+
+impl Deadline {
+    pub fn as_string(&self) -> String {
+        self.as_string_with(&Formats::default())
+    }
+
+    /// Same as `as_string`, with the date and time in `formats`. Times with seconds are written
+    /// in the canonical format followed by the seconds, which most time formats don't show.
+    pub fn as_string_with(&self, formats: &Formats) -> String {
+        let date = formats.date(self.date.naive_local());
+        match self.time {
+            Some(time) if time.second() != 0 => {
+                format!("{}@{}", date, time.format(&format!("{}:%S", format::TIME)))
+            }
+            Some(time) => format!("{}@{}", date, formats.time(time)),
+            None => date,
+        }
+    }
+
+    /// Human readable time left until the deadline, e.g. `due in 3 days` or `overdue by 2 hours`.
+    pub fn countdown(&self, now: &DateTime<Local>) -> String {
+        let left = self.as_datetime() - *now;
+        let (amount, unit) = if left.num_days().abs() > 0 {
+            (left.num_days(), "day")
+        } else if left.num_hours().abs() > 0 {
+            (left.num_hours(), "hour")
+        } else {
+            (left.num_minutes(), "minute")
+        };
+        let plural = if amount.abs() == 1 { "" } else { "s" };
+
+        if left < Duration::zero() {
+            format!("overdue by {} {}{}", -amount, unit, plural)
+        } else {
+            format!("due in {} {}{}", amount, unit, plural)
+        }
+    }
+}
@@ -1,74 +1,203 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{self, Debug, Formatter},
+    io,
     path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
 };
 
-use chrono::{Date, Datelike, Duration, Local};
+use chrono::{Date, DateTime, Datelike, Duration, Local, LocalResult, TimeZone, Timelike};
 use tokio::{
-    fs::OpenOptions,
+    fs::{self, OpenOptions},
     io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
 };
 
-use crate::{error::Error, setup::check_dir, task::Task};
+use crate::{
+    error::Error,
+    format::formats,
+    history,
+    parser::{duration_as_string, BINARY_MAGIC},
+    setup::check_dir,
+    storage,
+    task::{Task, TaskTime, Zone},
+};
+
+/// Contents of schedule files as they were last read or written, decrypted, by path. Only kept
+/// once `enable_cache` is called, as by `timaru shell` whose commands open the same schedules
+/// again and again.
+static CACHE: Mutex<Option<HashMap<PathBuf, Cached>>> = Mutex::new(None);
+
+/// Some filesystems only keep modification times to the second or two, so a file changed again
+/// within that time of being cached may keep the same one.
+const MTIME_PRECISION: std::time::Duration = std::time::Duration::from_secs(2);
+
+struct Cached {
+    /// Modification time and length of the file when it was cached.
+    modified: SystemTime,
+    len: u64,
+    cached_at: SystemTime,
+    content: Vec<u8>,
+}
+
+/// Keeps the contents of the schedules read or written from now on, so that opening them again
+/// only reads their file if it changed since.
+pub fn enable_cache() {
+    CACHE.lock().unwrap().get_or_insert_with(HashMap::new);
+}
+
+/// Modification time and length of the file at `path`.
+fn stat(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// The content of the schedule file at `path` kept by `enable_cache`, if it did not change since.
+/// Files modified too shortly before being cached are read again, as they could have changed
+/// since without their modification time changing.
+fn cached(path: &Path) -> Option<Vec<u8>> {
+    let cache = CACHE.lock().unwrap();
+    let cached = cache.as_ref()?.get(path)?;
+    let settled = matches!(
+        cached.cached_at.duration_since(cached.modified),
+        Ok(age) if age >= MTIME_PRECISION
+    );
+    if settled && stat(path)? == (cached.modified, cached.len) {
+        Some(cached.content.clone())
+    } else {
+        None
+    }
+}
+
+/// Keeps `content` as the one of the schedule file at `path`, if `enable_cache` was called.
+fn cache(path: &Path, content: &[u8]) {
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        match stat(path) {
+            Some((modified, len)) => cache.insert(
+                path.to_path_buf(),
+                Cached {
+                    modified,
+                    len,
+                    cached_at: SystemTime::now(),
+                    content: content.to_vec(),
+                },
+            ),
+            None => cache.remove(path),
+        };
+    }
+}
 
 #[derive(Clone)]
 pub struct Schedule {
     pub date: Date<Local>,
     pub tasks: HashMap<u8, Task>,
     pub file: PathBuf,
+    /// Tasks as they were when the schedule was read, by id, to find out which ones changed.
+    pub(crate) loaded: HashMap<String, String>,
+    /// Set for schedules loaded with `read`, which are not written back when dropped.
+    pub(crate) read_only: bool,
+    /// Decrypted content of the file as it was last read or written, so that it is not written
+    /// again unchanged.
+    pub(crate) stored: Option<Vec<u8>>,
 }
 
 impl Debug for Schedule {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        let mut s = format!(
-            "# {}-{}-{}\n",
-            self.date.day(),
-            self.date.month(),
-            self.date.year()
-        );
-
-        for (idx, task) in self.tasks.iter() {
-            s += &format!("({}) {}\n", idx, task.as_string());
-        }
-
-        f.write_str(&s)
+        f.write_str(&self.listing(&HashSet::new()))
     }
 }
 
 impl Schedule {
     pub async fn open(db_dir: &Path, date: Date<Local>) -> Result<Schedule, Error> {
         let schedule_path = check_dir(
-            check_dir(db_dir.join(&format!("{}", date.year())))
+            check_dir(db_dir.join(format!("{}", date.year())))
                 .await?
-                .join(&format!("{}", date.month())),
+                .join(format!("{}", date.month())),
         )
         .await?
-        .join(&format!("{}", date.day()));
+        .join(format!("{}", date.day()));
 
-        let mut schedule_file = BufReader::new(
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .open(&schedule_path)
-                .await?,
-        );
+        let schedule_content = match cached(&schedule_path) {
+            Some(schedule_content) => schedule_content,
+            None => {
+                let mut schedule_file = BufReader::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .truncate(false)
+                        .write(true)
+                        .read(true)
+                        .open(&schedule_path)
+                        .await?,
+                );
 
-        let mut schedule_content = String::new();
-        schedule_file.read_to_string(&mut schedule_content).await?;
+                let mut schedule_content = Vec::new();
+                schedule_file.read_to_end(&mut schedule_content).await?;
+                let schedule_content = storage::decode(&schedule_path, schedule_content)?;
+                cache(&schedule_path, &schedule_content);
+                schedule_content
+            }
+        };
+        Schedule::parse(schedule_path, date, schedule_content)
+    }
 
-        let schedule_content = schedule_content.trim();
+    /// Reads the schedule of `date` in `db_dir` like `open`, but without creating its file, a
+    /// missing file being an empty schedule. The schedule is never written back, so that reading
+    /// schedules does not change their files.
+    pub async fn read(db_dir: &Path, date: Date<Local>) -> Result<Schedule, Error> {
+        let schedule_path = Schedule::path(db_dir, &date);
+        let schedule_content = match cached(&schedule_path) {
+            Some(schedule_content) => schedule_content,
+            None => match fs::read(&schedule_path).await {
+                Ok(schedule_content) => {
+                    let schedule_content = storage::decode(&schedule_path, schedule_content)?;
+                    cache(&schedule_path, &schedule_content);
+                    schedule_content
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(err) => return Err(err.into()),
+            },
+        };
 
-        if schedule_content.is_empty() {
-            Ok(Schedule {
-                file: schedule_path,
-                tasks: Default::default(),
-                date,
-            })
+        let mut schedule = Schedule::parse(schedule_path, date, schedule_content)?;
+        schedule.read_only = true;
+        Ok(schedule)
+    }
+
+    /// Parses the decrypted content of the schedule file of `date` at `schedule_path`.
+    fn parse(
+        schedule_path: PathBuf,
+        date: Date<Local>,
+        schedule_content: Vec<u8>,
+    ) -> Result<Schedule, Error> {
+        let mut schedule = if schedule_content.starts_with(BINARY_MAGIC) {
+            Schedule::from_bytes(schedule_path, &schedule_content)?
         } else {
-            Schedule::from_str(schedule_path, &schedule_content)
-        }
+            let text = std::str::from_utf8(&schedule_content)
+                .map_err(|_| Error::Parse("schedule file is not valid UTF-8"))?
+                .trim();
+            if text.is_empty() {
+                Schedule {
+                    file: schedule_path,
+                    tasks: Default::default(),
+                    date,
+                    loaded: Default::default(),
+                    read_only: false,
+                    stored: None,
+                }
+            } else {
+                Schedule::from_str(schedule_path, text)?
+            }
+        };
+        schedule.stored = Some(schedule_content);
+        Ok(schedule)
+    }
+
+    /// Path of the schedule file of `date` in `db_dir`.
+    pub fn path(db_dir: &Path, date: &Date<Local>) -> PathBuf {
+        db_dir
+            .join(date.year().to_string())
+            .join(date.month().to_string())
+            .join(date.day().to_string())
     }
 
     pub async fn open_range(
@@ -92,7 +221,196 @@ impl Schedule {
         Ok(schedules)
     }
 
-    pub async fn sync(&self) -> Result<(), Error> {
+    /// Same as `open_range`, reading the schedules with `read`.
+    pub async fn read_range(
+        db_dir: &Path,
+        start_date: Date<Local>,
+        end_date: Date<Local>,
+    ) -> Result<Vec<Schedule>, Error> {
+        let mut schedules = Vec::new();
+        let mut day = start_date;
+        while day < end_date {
+            schedules.push(Schedule::read(db_dir, day).await?);
+            day = day + Duration::days(1);
+        }
+
+        Ok(schedules)
+    }
+
+    /// Dates of all the schedules stored in `db_dir`, in ascending order.
+    pub async fn stored_dates(db_dir: &Path) -> Result<Vec<Date<Local>>, Error> {
+        async fn numbered_entries(dir: &Path) -> Result<Vec<(u32, PathBuf)>, Error> {
+            let mut entries = Vec::new();
+            let mut read_dir = fs::read_dir(dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                if let Some(n) = entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                    entries.push((n, entry.path()));
+                }
+            }
+            Ok(entries)
+        }
+
+        let mut dates = Vec::new();
+
+        for (year, year_dir) in numbered_entries(db_dir).await? {
+            if !year_dir.is_dir() {
+                continue;
+            }
+            for (month, month_dir) in numbered_entries(&year_dir).await? {
+                if !month_dir.is_dir() {
+                    continue;
+                }
+                for (day, _) in numbered_entries(&month_dir).await? {
+                    if let LocalResult::Single(date) = Local.ymd_opt(year as i32, month, day) {
+                        dates.push(date);
+                    }
+                }
+            }
+        }
+
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// All the tasks stored in `db_dir`, along with the date and index of each task.
+    pub async fn all_tasks(db_dir: &Path) -> Result<Vec<(Date<Local>, u8, Task)>, Error> {
+        let mut tasks = Vec::new();
+
+        for date in Schedule::stored_dates(db_dir).await? {
+            let schedule = Schedule::read(db_dir, date).await?;
+            for (idx, task) in schedule.sorted_tasks() {
+                tasks.push((date, *idx, task.clone()));
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    /// Ids of all the unfinished tasks in `db_dir` which depend on a task that is not finished.
+    /// Dependencies on tasks that no longer exist are ignored.
+    pub async fn blocked_tasks(db_dir: &Path) -> Result<HashSet<String>, Error> {
+        Ok(Schedule::blocked_ids(&Schedule::all_tasks(db_dir).await?))
+    }
+
+    /// Same as `blocked_tasks`, but for tasks which are already loaded.
+    pub fn blocked_ids(tasks: &[(Date<Local>, u8, Task)]) -> HashSet<String> {
+        let unfinished: HashSet<_> = tasks
+            .iter()
+            .filter(|(_, _, task)| !task.finished)
+            .map(|(_, _, task)| task.id.as_str())
+            .collect();
+
+        tasks
+            .iter()
+            .filter(|(_, _, task)| {
+                !task.finished
+                    && task
+                        .dependencies
+                        .iter()
+                        .any(|id| unfinished.contains(id.as_str()))
+            })
+            .map(|(_, _, task)| task.id.clone())
+            .collect()
+    }
+
+    /// Ends all running work intervals in `db_dir` at `now`. Returns whether any task was being
+    /// worked on.
+    pub async fn stop_tracking(db_dir: &Path, now: DateTime<Local>) -> Result<bool, Error> {
+        let mut stopped = false;
+
+        for date in Schedule::stored_dates(db_dir).await? {
+            let mut schedule = Schedule::open(db_dir, date).await?;
+            for task in schedule.tasks.values_mut() {
+                for interval in task.intervals.iter_mut() {
+                    if interval.end.is_none() {
+                        interval.end = Some(now);
+                        stopped = true;
+                    }
+                }
+            }
+        }
+
+        Ok(stopped)
+    }
+
+    /// Sets the modification time of the tasks which changed since the schedule was read. The
+    /// time always increases, even for tasks changed twice within a second, so that `sync` can
+    /// tell that they changed.
+    fn stamp_modified(&mut self) {
+        let now = Local::now().with_nanosecond(0).unwrap();
+        for task in self.tasks.values_mut() {
+            if self.loaded.get(&task.id) != Some(&task.as_string()) {
+                task.modified = match task.modified {
+                    Some(modified) if modified >= now => Some(modified + Duration::seconds(1)),
+                    _ => Some(now),
+                };
+                self.loaded.insert(task.id.clone(), task.as_string());
+            }
+        }
+    }
+
+    /// Describes the changes to the tasks since the schedule was read, like `add 19-10-2026:
+    /// write report`, as the message of their history commit.
+    fn history_message(&self) -> String {
+        let date = format!(
+            "{}-{}-{}",
+            self.date.day(),
+            self.date.month(),
+            self.date.year()
+        );
+        let mut changes = Vec::new();
+        for (_, task) in self.sorted_tasks() {
+            match self.loaded.get(&task.id) {
+                None => changes.push(format!("add {}: {}", date, task.description)),
+                Some(loaded) if *loaded != task.as_string() => {
+                    changes.push(format!("update {}: {}", date, task.description))
+                }
+                _ => {}
+            }
+        }
+
+        let ids: HashSet<_> = self.tasks.values().map(|task| &task.id).collect();
+        let mut removed: Vec<_> = self
+            .loaded
+            .iter()
+            .filter(|(id, _)| !ids.contains(id))
+            .collect();
+        removed.sort();
+        for (id, loaded) in removed {
+            // The description of a task is at the end of its first line.
+            let description = loaded
+                .lines()
+                .next()
+                .and_then(|line| line.split_once("=> "))
+                .map_or(id.as_str(), |(_, description)| description);
+            changes.push(format!("remove {}: {}", date, description));
+        }
+
+        if changes.is_empty() {
+            format!("write {}", date)
+        } else {
+            changes.join("; ")
+        }
+    }
+
+    /// Keeps track of `content` having been written to the file, and commits it to the history.
+    fn written(&mut self, content: Vec<u8>, message: &str) -> Result<(), Error> {
+        cache(&self.file, &content);
+        self.stored = Some(content);
+        let tasks = &self.tasks;
+        self.loaded
+            .retain(|id, _| tasks.values().any(|task| task.id == *id));
+        history::record(&self.file, message)
+    }
+
+    pub async fn sync(&mut self) -> Result<(), Error> {
+        let message = self.history_message();
+        self.stamp_modified();
+        let content = storage::content(self);
+        if self.stored.as_ref() == Some(&content) {
+            return Ok(());
+        }
+
         let mut schedule_file = BufWriter::new(
             OpenOptions::new()
                 .create(true)
@@ -102,16 +420,26 @@ impl Schedule {
                 .await?,
         );
 
-        schedule_file.write_all(self.as_string().as_bytes()).await?;
-        Ok(())
+        schedule_file
+            .write_all(&storage::seal(&self.file, content.clone()))
+            .await?;
+        schedule_file.flush().await?;
+        self.written(content, &message)
     }
 
-    pub fn flush(&self) -> Result<(), Error> {
+    pub fn flush(&mut self) -> Result<(), Error> {
         use std::{
             fs,
             io::{self, Write},
         };
 
+        let message = self.history_message();
+        self.stamp_modified();
+        let content = storage::content(self);
+        if self.stored.as_ref() == Some(&content) {
+            return Ok(());
+        }
+
         let mut schedule_file = io::BufWriter::new(
             fs::OpenOptions::new()
                 .create(true)
@@ -120,14 +448,105 @@ impl Schedule {
                 .open(&self.file)?,
         );
 
-        schedule_file.write_all(self.as_string().as_bytes())?;
-        Ok(())
+        schedule_file.write_all(&storage::seal(&self.file, content.clone()))?;
+        schedule_file.flush()?;
+        drop(schedule_file);
+        self.written(content, &message)
     }
 
+    /// The schedule as shown to the user, with tasks whose ids are in `blocked` marked as such.
+    pub fn listing(&self, blocked: &HashSet<String>) -> String {
+        self.listing_in(blocked, None)
+    }
+
+    /// Same as `listing`, with the times of tasks on the clocks of `zone`, or the local ones if
+    /// none is given. Tasks which fall on another day there are marked with that day.
+    pub fn listing_in(&self, blocked: &HashSet<String>, zone: Option<&Zone>) -> String {
+        let formats = formats();
+        let mut s = format!("# {}\n", formats.date(self.date.naive_local()));
+
+        let now = Local::now();
+
+        for (idx, task) in self.sorted_tasks() {
+            s += &format!("({}) ", idx);
+            if blocked.contains(&task.id) {
+                s += "[blocked] ";
+            }
+            s += &task.header_as_string_with(zone, &formats);
+            let dates = match &task.time {
+                TaskTime::Precise { time } => Some((time, time)),
+                TaskTime::Period { start, end } => Some((start, end)),
+                _ => None,
+            }
+            .map(|(start, end)| {
+                (
+                    Zone::wall_clock_of(zone, start).date(),
+                    Zone::wall_clock_of(zone, end).date(),
+                )
+            });
+            match dates {
+                Some((start, end)) if start != end => {
+                    s += &format!(" (on {} - {})", formats.date(start), formats.date(end));
+                }
+                Some((start, _)) if start != self.date.naive_local() => {
+                    s += &format!(" (on {})", formats.date(start));
+                }
+                _ => {}
+            }
+            if let (Some(deadline), false) = (&task.deadline, task.finished) {
+                s += &format!(" ({})", deadline.countdown(&now));
+            }
+            if !task.intervals.is_empty() {
+                s += &format!(
+                    " [{}tracked {}",
+                    if task.is_running() { "running, " } else { "" },
+                    duration_as_string(&task.tracked_duration(&now))
+                );
+                if let Some(planned) = task.time.planned_duration() {
+                    s += &format!(" / planned {}", duration_as_string(&planned));
+                }
+                s += "]";
+            }
+            s += &format!("{}\n", task.children_as_string());
+        }
+
+        s
+    }
+
+    /// Tasks of the schedule along with their indices, in the order they should be listed.
+    pub fn sorted_tasks(&self) -> Vec<(&u8, &Task)> {
+        let mut tasks: Vec<_> = self.tasks.iter().collect();
+        tasks.sort_by(|(a_idx, a), (b_idx, b)| a.schedule_cmp(b).then(a_idx.cmp(b_idx)));
+        tasks
+    }
+
+    /// Adds a task after the last one, failing with `Error::Idx` once the last index is 255.
     #[inline]
-    pub fn add_task(&mut self, task: Task) {
-        let idx = self.tasks.len() as u8 + 1;
+    pub fn add_task(&mut self, task: Task) -> Result<(), Error> {
+        // Indices are not shifted on removal, so the number of tasks might already be in use.
+        let idx = match self.tasks.keys().max() {
+            Some(idx) => idx.checked_add(1).ok_or(Error::Idx)?,
+            None => 1,
+        };
         self.tasks.insert(idx, task);
+        Ok(())
+    }
+
+    /// Adds a task copied from another copy of the database, keeping its modification time.
+    pub fn add_synced_task(&mut self, task: Task) -> Result<(), Error> {
+        let loaded = (task.id.clone(), task.as_string());
+        self.add_task(task)?;
+        self.loaded.insert(loaded.0, loaded.1);
+        Ok(())
+    }
+
+    /// Index of the task with the given id in this schedule.
+    #[inline]
+    pub fn find_task(&self, id: &str) -> Option<u8> {
+        self.tasks
+            .iter()
+            .find(|(_, task)| task.id == id)
+            .map(|(idx, _)| *idx)
     }
 
     #[inline]
@@ -138,6 +557,202 @@ impl Schedule {
 
 impl Drop for Schedule {
     fn drop(&mut self) {
-        self.flush().unwrap();
+        if !self.read_only {
+            self.flush().unwrap();
+        }
+    }
+}
+
+mod test {
+    #[test]
+    fn test_blocked_ids() {
+        use std::collections::HashSet;
+
+        use chrono::{Local, TimeZone};
+
+        use super::Schedule;
+        use crate::task::Task;
+
+        let date = Local.ymd(2026, 10, 19);
+        let tasks: Vec<_> = [
+            "* [ ] 9:00 {id:review} => review",
+            "* [ ] 10:00 {id:deploy after:review} => deploy",
+            "* [X] 11:00 {id:tag after:review} => tag",
+            "* [ ] 12:00 {id:announce after:gone} => announce",
+            "* [ ] 13:00 {id:ping after:pong} => ping",
+            "* [ ] 14:00 {id:pong after:ping} => pong",
+            "* [X] 15:00 {id:draft} => draft",
+            "* [ ] 16:00 {id:publish after:draft} => publish",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| (date, i as u8 + 1, Task::from_str(line, &date).unwrap()))
+        .collect();
+
+        // Finished tasks and dependencies on finished or missing tasks do not block, while tasks
+        // depending on each other block each other.
+        let expected: HashSet<_> = ["deploy", "ping", "pong"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        assert_eq!(Schedule::blocked_ids(&tasks), expected);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_schedule_not_written() {
+        use std::{
+            env,
+            time::{Duration, SystemTime},
+        };
+
+        use chrono::{Local, TimeZone};
+        use tokio::fs;
+
+        use super::Schedule;
+        use crate::task::{Task, TaskTime};
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-unchanged-{}", std::process::id()));
+        fs::create_dir_all(&db_dir).await.unwrap();
+        let date = Local.ymd(2026, 10, 19);
+        let mut schedule = Schedule::open(&db_dir, date).await.unwrap();
+        schedule
+            .add_task(Task::new(
+                TaskTime::Precise {
+                    time: date.and_hms(9, 0, 0),
+                },
+                "write report".to_string(),
+            ))
+            .unwrap();
+        drop(schedule);
+
+        let path = Schedule::path(&db_dir, &date);
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let set_old = || {
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(old)
+        };
+        let modified = || std::fs::metadata(&path).unwrap().modified().unwrap();
+        set_old().unwrap();
+
+        drop(Schedule::open(&db_dir, date).await.unwrap());
+        assert_eq!(modified(), old);
+
+        let mut schedule = Schedule::open(&db_dir, date).await.unwrap();
+        schedule.tasks.get_mut(&1).unwrap().description = "write the report".to_string();
+        drop(schedule);
+        assert_ne!(modified(), old);
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+
+    #[test]
+    fn test_listing_in_zone() {
+        use std::{collections::HashSet, env, str::FromStr};
+
+        use super::Schedule;
+        use crate::task::Zone;
+
+        let file = env::temp_dir().join(format!("timaru-test-listing-{}", std::process::id()));
+        let mut schedule = Schedule::from_str(
+            file,
+            "# 19-10-2026 v3
+* [ ] 9:00 {id:1 tz:+00:00} => standup
+* [ ] 22:00 - 23:30 {id:2 tz:+00:00} => call",
+        )
+        .unwrap();
+        schedule.read_only = true;
+        let blocked = HashSet::new();
+
+        let listing = schedule.listing_in(&blocked, Some(&Zone::from_str("+01:00").unwrap()));
+        let lines: Vec<_> = listing.lines().collect();
+        assert!(!lines[1].contains("(on "), "{}", lines[1]);
+        assert!(
+            lines[2].ends_with("(on 19-10-2026 - 20-10-2026)"),
+            "{}",
+            lines[2]
+        );
+
+        let listing = schedule.listing_in(&blocked, Some(&Zone::from_str("+05:00").unwrap()));
+        let lines: Vec<_> = listing.lines().collect();
+        assert!(lines[2].ends_with("(on 20-10-2026)"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_add_task_after_last_index() {
+        use std::env;
+
+        use super::Schedule;
+        use crate::error::Error;
+
+        let file = env::temp_dir().join(format!("timaru-test-add-{}", std::process::id()));
+        let mut schedule = Schedule::from_str(
+            file,
+            "# 19-10-2026 v3
+* [ ] 9:00 {id:1} => standup
+* [ ] 10:00 {id:2} => review",
+        )
+        .unwrap();
+        schedule.read_only = true;
+
+        // Indices are not reused, so a schedule whose last index is 255 is full.
+        let task = schedule.remove_task(2).unwrap();
+        schedule.tasks.insert(255, task.clone());
+        assert!(matches!(schedule.add_task(task.clone()), Err(Error::Idx)));
+        assert_eq!(schedule.tasks.len(), 2);
+        schedule.remove_task(255);
+        schedule.add_task(task).unwrap();
+        assert_eq!(schedule.tasks[&2].description, "review");
+    }
+
+    #[tokio::test]
+    async fn test_stop_tracking() {
+        use std::env;
+
+        use chrono::{Duration, Local, TimeZone};
+        use tokio::fs;
+
+        use super::Schedule;
+        use crate::task::{Task, TaskTime, WorkInterval};
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-tracking-{}", std::process::id()));
+        fs::create_dir_all(&db_dir).await.unwrap();
+        let yesterday = Local.ymd(2026, 10, 18);
+        let today = Local.ymd(2026, 10, 19);
+        let now = today.and_hms(10, 0, 0);
+        for (date, end) in [
+            (yesterday, Some(yesterday.and_hms(23, 0, 0))),
+            (today, None),
+        ] {
+            let mut schedule = Schedule::open(&db_dir, date).await.unwrap();
+            let mut task = Task::new(
+                TaskTime::Precise {
+                    time: date.and_hms(9, 0, 0),
+                },
+                "write report".to_string(),
+            );
+            task.intervals.push(WorkInterval {
+                start: date.and_hms(22, 0, 0) - Duration::days(1),
+                end,
+            });
+            schedule.add_task(task).unwrap();
+        }
+
+        assert!(Schedule::stop_tracking(&db_dir, now).await.unwrap());
+        let schedule = Schedule::read(&db_dir, yesterday).await.unwrap();
+        assert_eq!(
+            schedule.tasks[&1].intervals[0].end,
+            Some(yesterday.and_hms(23, 0, 0))
+        );
+        let schedule = Schedule::read(&db_dir, today).await.unwrap();
+        assert_eq!(schedule.tasks[&1].intervals[0].end, Some(now));
+        assert!(!schedule.tasks[&1].is_running());
+
+        // Nothing is left running.
+        assert!(!Schedule::stop_tracking(&db_dir, now).await.unwrap());
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
     }
 }
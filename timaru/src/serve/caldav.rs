@@ -0,0 +1,480 @@
+//! A CalDAV server exposing the schedules as a single calendar collection.
+//!
+//! Each task is a `VTODO` resource named `<id>.ics` in the collection at `/calendars/timaru/`.
+//! Clients discover the collection through `/principal/`, list it with `PROPFIND` or `REPORT`,
+//! and read, write and delete tasks with `GET`, `PUT` and `DELETE`. `PUT` accepts `VTODO` and
+//! `VEVENT` components, creating a task, or updating the task with the id of the resource.
+//! `PUT` and `DELETE` honour `If-Match` and `If-None-Match`, so that a client can't overwrite a
+//! change it has not seen.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    convert::Infallible,
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use chrono::{Date, Local};
+use hyper::{
+    body,
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use tokio::sync::Mutex;
+
+use crate::{error::Error, export::import_tasks, history, schedule::Schedule, task::Task};
+
+const PRINCIPAL: &str = "/principal/";
+const HOME: &str = "/calendars/";
+const COLLECTION: &str = "/calendars/timaru/";
+
+struct CalDav {
+    db_dir: PathBuf,
+    /// Held while handling a request, as schedules are read and written as whole files.
+    lock: Mutex<()>,
+}
+
+/// Serves the schedules of `db_dir` over CalDAV on `localhost:port` until interrupted.
+pub async fn serve_caldav(db_dir: PathBuf, port: u16) -> Result<(), Error> {
+    let caldav = Arc::new(CalDav {
+        db_dir,
+        lock: Mutex::new(()),
+    });
+    let make_service = make_service_fn(move |_| {
+        let caldav = caldav.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let caldav = caldav.clone();
+                async move { Ok::<_, Infallible>(caldav.respond(req).await) }
+            }))
+        }
+    });
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    println!("serving CalDAV collection at http://{}{}", addr, COLLECTION);
+    Server::bind(&addr).serve(make_service).await?;
+
+    Ok(())
+}
+
+/// Quoted hash of `value`, used as `ETag` of tasks and `getctag` of the collection.
+fn etag<T: Hash>(value: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// The `ETag` of a task, which changes whenever the task or its date changes.
+fn task_etag(date: &Date<Local>, task: &Task) -> String {
+    etag(&(date.to_string(), task.as_string()))
+}
+
+fn escape_xml(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A `response` element of a multistatus body with the properties of `href`.
+fn prop_response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>\n",
+        href, props
+    )
+}
+
+fn multistatus(responses: String) -> Response<Body> {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<d:multistatus xmlns:d=\"DAV:\" \
+         xmlns:c=\"urn:ietf:params:xml:ns:caldav\" xmlns:cs=\"http://calendarserver.org/ns/\">\n\
+         {}</d:multistatus>\n",
+        responses
+    );
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::MULTI_STATUS;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    response
+}
+
+fn status(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+/// The `href` values of an XML body, as sent in `calendar-multiget` reports and `PROPFIND`
+/// responses.
+pub(crate) fn hrefs(body: &str) -> Vec<String> {
+    body.split('<')
+        .filter_map(|element| {
+            let (tag, text) = element.split_at(element.find('>')?);
+            let name = tag.split_whitespace().next()?;
+            if name == "href" || name.ends_with(":href") {
+                Some(text[1..].trim().to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Decodes the `%XX` escapes of a path segment.
+fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut input = input.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [input.next()?, input.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The id of the task of a resource path like `/calendars/timaru/<id>.ics`. Clients name the
+/// resources they create, often as `<uid>.ics` with a `<uuid>@<host>` UID, so any name which can
+/// be written as a task id is accepted.
+fn task_id(path: &str) -> Option<String> {
+    let name = percent_decode(path.rsplit('/').next()?)?;
+    let name = name.strip_suffix(".ics")?;
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c))
+    {
+        Some(name.to_string())
+    } else {
+        None
+    }
+}
+
+/// Whether the `If-Match` and `If-None-Match` headers of a request allow changing a resource
+/// whose `ETag` is `etag`, or which does not exist if it is `None`.
+fn preconditions_met(headers: &HeaderMap, etag: Option<&str>) -> bool {
+    let matches = |header: &HeaderValue| match header.to_str() {
+        Ok(tags) => tags
+            .split(',')
+            .map(str::trim)
+            .any(|tag| etag.is_some() && (tag == "*" || Some(tag) == etag)),
+        Err(_) => false,
+    };
+    headers.get(IF_MATCH).map(matches) != Some(false)
+        && headers.get(IF_NONE_MATCH).map(matches) != Some(true)
+}
+
+impl CalDav {
+    async fn respond(&self, req: Request<Body>) -> Response<Body> {
+        let _lock = self.lock.lock().await;
+        match self.handle(req).await {
+            Ok(response) => response,
+            Err(err @ Error::Parse(_)) => status(StatusCode::BAD_REQUEST, err.to_string()),
+            Err(err @ Error::NoTask(_)) => status(StatusCode::NOT_FOUND, err.to_string()),
+            Err(err) => status(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+        }
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        let path = req.uri().path().to_string();
+        let headers = req.headers().clone();
+        // Members are listed for a `Depth` of `1` or `infinity`, the default of `PROPFIND`.
+        let with_members = req.headers().get("Depth").map(|depth| depth.as_bytes()) != Some(b"0");
+        let method = req.method().clone();
+        let body = body::to_bytes(req.into_body()).await?;
+        let body = String::from_utf8_lossy(&body);
+
+        // Requests which don't need the tasks are answered without reading the schedules.
+        match (method.as_str(), path.as_str()) {
+            ("OPTIONS", _) => {
+                let mut response = status(StatusCode::OK, String::new());
+                let headers = response.headers_mut();
+                headers.insert("DAV", HeaderValue::from_static("1, calendar-access"));
+                headers.insert(
+                    "Allow",
+                    HeaderValue::from_static("OPTIONS, PROPFIND, REPORT, GET, PUT, DELETE"),
+                );
+                return Ok(response);
+            }
+            ("PROPFIND", "/") | ("PROPFIND", PRINCIPAL) => {
+                return Ok(multistatus(prop_response(&path, &self.principal_props())));
+            }
+            _ => {}
+        }
+
+        let tasks = Schedule::all_tasks(&self.db_dir).await?;
+        let find = |id: &str| {
+            tasks
+                .iter()
+                .find(|(_, _, task)| task.id == id)
+                .map(|(date, idx, task)| (*date, *idx, task))
+        };
+
+        let id = task_id(&path);
+        // Changes are refused if the client has not seen the current version of the task.
+        if let ("PUT", Some(id)) | ("DELETE", Some(id)) = (method.as_str(), &id) {
+            let etag = find(id).map(|(date, _, task)| task_etag(&date, task));
+            if !preconditions_met(&headers, etag.as_deref()) {
+                return Ok(status(StatusCode::PRECONDITION_FAILED, String::new()));
+            }
+        }
+
+        let response = match (method.as_str(), path.as_str(), id.as_deref()) {
+            ("PROPFIND", HOME, _) => {
+                let mut responses =
+                    prop_response(HOME, "<d:resourcetype><d:collection/></d:resourcetype>");
+                if with_members {
+                    responses += &prop_response(COLLECTION, &self.collection_props(&tasks));
+                }
+                multistatus(responses)
+            }
+            ("PROPFIND", COLLECTION, _) => {
+                let mut responses = prop_response(COLLECTION, &self.collection_props(&tasks));
+                if with_members {
+                    for (date, _, task) in tasks.iter() {
+                        responses += &self.task_response(date, task, false);
+                    }
+                }
+                multistatus(responses)
+            }
+            ("PROPFIND", _, Some(id)) => match find(id) {
+                Some((date, _, task)) => multistatus(self.task_response(&date, task, false)),
+                None => return Err(Error::NoTask(id.to_string())),
+            },
+            ("REPORT", COLLECTION, _) => {
+                let mut responses = String::new();
+                if body.contains("calendar-multiget") {
+                    for id in hrefs(&body).iter().filter_map(|href| task_id(href)) {
+                        if let Some((date, _, task)) = find(&id) {
+                            responses += &self.task_response(&date, task, true);
+                        }
+                    }
+                } else {
+                    // Filters of `calendar-query` are not applied, clients filter the tasks.
+                    for (date, _, task) in tasks.iter() {
+                        responses += &self.task_response(date, task, true);
+                    }
+                }
+                multistatus(responses)
+            }
+            ("GET", _, Some(id)) => match find(id) {
+                Some((date, _, task)) => {
+                    let mut response = status(StatusCode::OK, task.as_ical(&date));
+                    let headers = response.headers_mut();
+                    headers.insert(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("text/calendar; charset=utf-8"),
+                    );
+                    headers.insert(
+                        ETAG,
+                        HeaderValue::from_str(&task_etag(&date, task)).unwrap(),
+                    );
+                    response
+                }
+                None => return Err(Error::NoTask(id.to_string())),
+            },
+            ("PUT", _, Some(id)) => {
+                let (date, task) = Task::from_ical(&body)?;
+                let exists = find(id).is_some();
+                let task = Task {
+                    id: id.to_string(),
+                    ..task
+                };
+                let message = format!("caldav: put {}", task.description);
+                let changes =
+                    import_tasks(&self.db_dir, vec![(date, task)], false, |old, new| Task {
+                        time: new.time,
+                        description: new.description,
+                        pomodoro: new.pomodoro,
+                        priority: new.priority,
+                        deadline: new.deadline,
+                        dependencies: new.dependencies,
+                        notes: new.notes,
+                        finished: new.finished,
+                        done: new.done.or(old.done),
+                        tz: new.tz,
+                        ..old
+                    });
+                history::batch(&self.db_dir, &message, changes).await?;
+
+                let status_code = if exists {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::CREATED
+                };
+                let mut response = status(status_code, String::new());
+                if let Some((date, _, task)) = Schedule::all_tasks(&self.db_dir)
+                    .await?
+                    .into_iter()
+                    .find(|(_, _, task)| task.id == id)
+                {
+                    let etag = task_etag(&date, &task);
+                    response
+                        .headers_mut()
+                        .insert(ETAG, HeaderValue::from_str(&etag).unwrap());
+                }
+                response
+            }
+            ("DELETE", _, Some(id)) => match find(id) {
+                Some((date, idx, task)) => {
+                    let changes = async {
+                        Schedule::open(&self.db_dir, date).await?.remove_task(idx);
+                        Ok(())
+                    };
+                    let message = format!("caldav: delete {}", task.description);
+                    history::batch(&self.db_dir, &message, changes).await?;
+                    status(StatusCode::NO_CONTENT, String::new())
+                }
+                None => return Err(Error::NoTask(id.to_string())),
+            },
+            _ => status(StatusCode::METHOD_NOT_ALLOWED, String::new()),
+        };
+
+        Ok(response)
+    }
+
+    fn principal_props(&self) -> String {
+        format!(
+            "<d:resourcetype><d:collection/><d:principal/></d:resourcetype>\
+             <d:current-user-principal><d:href>{}</d:href></d:current-user-principal>\
+             <c:calendar-home-set><d:href>{}</d:href></c:calendar-home-set>",
+            PRINCIPAL, HOME
+        )
+    }
+
+    fn collection_props(&self, tasks: &[(Date<Local>, u8, Task)]) -> String {
+        let etags: Vec<_> = tasks
+            .iter()
+            .map(|(date, _, task)| task_etag(date, task))
+            .collect();
+        format!(
+            "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+             <d:displayname>timaru</d:displayname>\
+             <c:supported-calendar-component-set><c:comp name=\"VTODO\"/>\
+             <c:comp name=\"VEVENT\"/></c:supported-calendar-component-set>\
+             <d:current-user-principal><d:href>{}</d:href></d:current-user-principal>\
+             <d:current-user-privilege-set><d:privilege><d:all/></d:privilege>\
+             </d:current-user-privilege-set><cs:getctag>{}</cs:getctag>",
+            PRINCIPAL,
+            etag(&etags)
+        )
+    }
+
+    /// The properties of a task, along with its iCalendar object if `with_data` is set.
+    fn task_response(&self, date: &Date<Local>, task: &Task, with_data: bool) -> String {
+        let mut props = format!(
+            "<d:resourcetype/><d:getetag>{}</d:getetag>\
+             <d:getcontenttype>text/calendar; charset=utf-8; component=vtodo</d:getcontenttype>",
+            task_etag(date, task)
+        );
+        if with_data {
+            props += &format!(
+                "<c:calendar-data>{}</c:calendar-data>",
+                escape_xml(&task.as_ical(date))
+            );
+        }
+        prop_response(&format!("{}{}.ics", COLLECTION, task.id), &props)
+    }
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_caldav_requests() {
+        use std::{env, str::FromStr};
+
+        use chrono::{Local, NaiveDate, TimeZone};
+        use hyper::{body, header::ETAG, Body, Request, Response, StatusCode};
+        use tokio::{fs, sync::Mutex};
+
+        use super::{task_etag, CalDav};
+        use crate::{
+            schedule::Schedule,
+            task::{TaskTime, Zone},
+        };
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-caldav-{}", std::process::id()));
+        let date = Local.ymd(2026, 10, 19);
+        let path = Schedule::path(&db_dir, &date);
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        // A schedule of version 1, whose task gets its id when read.
+        fs::write(&path, "# 19-10-2026\n* [ ] 9:00 => write report")
+            .await
+            .unwrap();
+        let caldav = CalDav {
+            db_dir: db_dir.clone(),
+            lock: Mutex::new(()),
+        };
+        let request = |method: &str, uri: &str, headers: &[(&str, &str)], body: &str| {
+            let mut request = Request::builder().method(method).uri(uri);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            caldav.respond(request.body(Body::from(body.to_string())).unwrap())
+        };
+        let text = |response: Response<Body>| async {
+            String::from_utf8(body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+        };
+
+        // The task keeps the same resource name until its schedule is written.
+        let listing = text(request("PROPFIND", "/calendars/timaru/", &[], "").await).await;
+        assert_eq!(
+            text(request("PROPFIND", "/calendars/timaru/", &[], "").await).await,
+            listing
+        );
+        let id = Schedule::read(&db_dir, date).await.unwrap().tasks[&1]
+            .id
+            .clone();
+        let href = format!("/calendars/timaru/{}.ics", id);
+        assert!(listing.contains(&href), "{}", listing);
+
+        let response = request("GET", &href, &[], "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+        let ical = text(response)
+            .await
+            .replace("write report", "write the report");
+
+        // Changes made without having seen the current version of the task are refused.
+        let stale = [("If-Match", "\"0000000000000000\"")];
+        let response = request("PUT", &href, &stale, &ical).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let response = request("PUT", &href, &[("If-None-Match", "*")], &ical).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let response = request("PUT", &href, &[("If-Match", &etag)], &ical).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = request("DELETE", &href, &[("If-Match", &etag)], "").await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        assert_eq!(schedule.tasks[&1].description, "write the report");
+        let etag = task_etag(&date, &schedule.tasks[&1]);
+        let response = request("DELETE", &href, &[("If-Match", &etag)], "").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Resources named by clients are kept, and times on the clocks of their `TZID`.
+        let event = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1a2b@example.com\r\n\
+                     DTSTART;TZID=America/New_York:20261019T090000\r\nSUMMARY:call\r\n\
+                     END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let href = "/calendars/timaru/1a2b%40example.com.ics";
+        let response = request("PUT", href, &[("If-None-Match", "*")], event).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(request("GET", href, &[], "").await.status(), StatusCode::OK);
+        let schedule = Schedule::read(&db_dir, date).await.unwrap();
+        let task = schedule.tasks.values().next().unwrap();
+        assert_eq!(task.id, "1a2b@example.com");
+        let new_york = Zone::from_str("America/New_York").unwrap();
+        let time = new_york
+            .at_wall_clock(&NaiveDate::from_ymd(2026, 10, 19).and_hms(9, 0, 0))
+            .unwrap();
+        assert!(matches!(task.time, TaskTime::Precise { time: t } if t == time));
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -0,0 +1,636 @@
+//! A JSON API over HTTP for other applications, like dashboards and editor plugins.
+//!
+//! Changes are made by running the same `SubCommand`s as the CLI, and respond with the schedule
+//! of the changed date. Tasks are addressed by the date of their schedule and their index, as in
+//! `timaru update <date> <idx>`.
+
+use std::{
+    collections::HashMap, convert::Infallible, future::Future, net::SocketAddr, path::PathBuf,
+    pin::Pin, str::FromStr, sync::Arc,
+};
+
+use chrono::{Datelike, Local};
+use hyper::{
+    body,
+    header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    cli::{PomodoroUpdate, SubCommand, UpdateSubCmd},
+    error::Error,
+    format::formats,
+    parser::get_date,
+    schedule::Schedule,
+    task::{Deadline, Priority, Task, TaskTime, Zone},
+};
+
+/// Dates of the API are always in the canonical format, while commands read them in the one of
+/// the config file first, so they are given to commands in the latter.
+fn command_date(date: &str) -> Result<String, Error> {
+    Ok(formats().date(get_date(date)?.naive_local()))
+}
+
+/// Same as `command_date`, for a deadline.
+fn command_deadline(due: &str) -> Result<String, Error> {
+    Ok(Deadline::from_str(due)?.as_string_with(&formats()))
+}
+
+struct Api {
+    db_dir: PathBuf,
+    token: Option<String>,
+    /// Held while handling a request, as schedules are read and written as whole files.
+    lock: Mutex<()>,
+}
+
+#[derive(Serialize)]
+struct ScheduleJson<'a> {
+    date: String,
+    tasks: Vec<TaskJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskJson<'a> {
+    idx: u8,
+    id: &'a str,
+    time: String,
+    description: &'a str,
+    finished: bool,
+    priority: Option<&'a str>,
+    pomodoro: Option<PomodoroJson>,
+    due: Option<String>,
+    tz: Option<String>,
+    dependencies: &'a [String],
+    subtasks: Vec<SubtaskJson<'a>>,
+    notes: &'a [String],
+    tracked_minutes: i64,
+}
+
+#[derive(Serialize)]
+struct PomodoroJson {
+    total: u8,
+    done: u8,
+}
+
+#[derive(Serialize)]
+struct SubtaskJson<'a> {
+    description: &'a str,
+    finished: bool,
+}
+
+/// Body of `POST /tasks`, with the same fields as `timaru add`.
+#[derive(Deserialize)]
+struct AddBody {
+    date: Option<String>,
+    time: Option<String>,
+    pomodoro: Option<u8>,
+    priority: Option<String>,
+    #[serde(default)]
+    after: Vec<String>,
+    due: Option<String>,
+    tz: Option<String>,
+    description: String,
+}
+
+/// Body of `PATCH /tasks/<date>/<idx>`. An empty `priority` or `due` removes it.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct UpdateBody {
+    date: Option<String>,
+    time: Option<String>,
+    description: Option<String>,
+    priority: Option<String>,
+    due: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ToggleBody {
+    force: bool,
+}
+
+/// Body of `POST /tasks/<date>/<idx>/pomodoro`. An empty body marks one more cycle as done.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PomodoroBody {
+    total: Option<u8>,
+    done: Option<u8>,
+    remove: bool,
+}
+
+/// Serves the JSON API for the schedules of `db_dir` on `localhost:port` until interrupted.
+/// Requests have to carry `token` as bearer token if it is set.
+pub async fn serve_http(db_dir: PathBuf, port: u16, token: Option<String>) -> Result<(), Error> {
+    let api = Arc::new(Api {
+        db_dir,
+        token,
+        lock: Mutex::new(()),
+    });
+    let make_service = make_service_fn(move |_| {
+        let api = api.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let api = api.clone();
+                async move { Ok::<_, Infallible>(api.respond(req).await) }
+            }))
+        }
+    });
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    println!("serving JSON API at http://{}", addr);
+    Server::bind(&addr).serve(make_service).await?;
+
+    Ok(())
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Result<Response<Body>, Error> {
+    let mut response = Response::new(Body::from(serde_json::to_string(value)?));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(response)
+}
+
+fn json_error(status: StatusCode, message: String) -> Response<Body> {
+    let body = serde_json::json!({ "error": message }).to_string();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Parses a JSON body, an empty body being the default value.
+fn parse_body<T: DeserializeOwned + Default>(body: &[u8]) -> Result<T, Error> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        Ok(T::default())
+    } else {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+/// Decodes the `%XX` escapes of a query string component, and its `+` as spaces.
+fn percent_decode(s: &str) -> Result<String, Error> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'%' => {
+                let hex = [input.next(), input.next()];
+                let hex = match hex {
+                    [Some(high), Some(low)] => String::from_utf8(vec![high, low]).ok(),
+                    _ => None,
+                };
+                match hex.and_then(|hex| u8::from_str_radix(&hex, 16).ok()) {
+                    Some(byte) => bytes.push(byte),
+                    None => return Err(Error::Parse("invalid escape in query string")),
+                }
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| Error::Parse("query string is not valid UTF-8"))
+}
+
+fn parse_query(query: Option<&str>) -> Result<HashMap<String, String>, Error> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            Ok((
+                percent_decode(parts.next().unwrap_or_default())?,
+                percent_decode(parts.next().unwrap_or_default())?,
+            ))
+        })
+        .collect()
+}
+
+/// Compares `a` and `b` in a time which does not depend on where they differ, so that the token
+/// cannot be guessed one byte at a time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl<'a> ScheduleJson<'a> {
+    fn new(schedule: &'a Schedule) -> ScheduleJson<'a> {
+        let now = Local::now();
+        let date = schedule.date;
+        ScheduleJson {
+            date: format!("{}-{}-{}", date.day(), date.month(), date.year()),
+            tasks: schedule
+                .sorted_tasks()
+                .into_iter()
+                .map(|(idx, task)| TaskJson {
+                    idx: *idx,
+                    id: &task.id,
+                    time: task.time.as_string_in(task.tz.as_ref()),
+                    description: &task.description,
+                    finished: task.finished,
+                    priority: task.priority.as_ref().map(Priority::as_str),
+                    pomodoro: task
+                        .pomodoro
+                        .map(|(total, done)| PomodoroJson { total, done }),
+                    due: task.deadline.as_ref().map(|deadline| deadline.as_string()),
+                    tz: task.tz.as_ref().map(Zone::as_string),
+                    dependencies: &task.dependencies,
+                    subtasks: task
+                        .subtasks
+                        .iter()
+                        .map(|subtask| SubtaskJson {
+                            description: &subtask.description,
+                            finished: subtask.finished,
+                        })
+                        .collect(),
+                    notes: &task.notes,
+                    tracked_minutes: task.tracked_duration(&now).num_minutes(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Api {
+    async fn respond(&self, req: Request<Body>) -> Response<Body> {
+        if let Some(token) = &self.token {
+            let authorized = req.headers().get(AUTHORIZATION).map(|value| {
+                constant_time_eq(value.as_bytes(), format!("Bearer {}", token).as_bytes())
+            }) == Some(true);
+            if !authorized {
+                return json_error(StatusCode::UNAUTHORIZED, "invalid bearer token".into());
+            }
+        }
+
+        let _lock = self.lock.lock().await;
+        match self.handle(req).await {
+            Ok(response) => response,
+            Err(err) => {
+                let status = match err {
+                    Error::Parse(_) | Error::Json(_) => StatusCode::BAD_REQUEST,
+                    Error::Idx | Error::NoTask(_) => StatusCode::NOT_FOUND,
+                    Error::Blocked(_) => StatusCode::CONFLICT,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                json_error(status, err.to_string())
+            }
+        }
+    }
+
+    /// The schedule of `date`, or of today if not provided, as JSON.
+    async fn schedule(
+        &self,
+        date: Option<&str>,
+        status: StatusCode,
+    ) -> Result<Response<Body>, Error> {
+        let date = match date {
+            Some(date) => get_date(date)?,
+            None => Local::today(),
+        };
+        let schedule = Schedule::read(&self.db_dir, date).await?;
+        json(status, &ScheduleJson::new(&schedule))
+    }
+
+    /// Runs `subcmd` as the CLI does. The future is boxed as `SubCommand::parse` can itself
+    /// start this server, which would otherwise stop the compiler from proving it is `Send`.
+    fn run(
+        &self,
+        subcmd: SubCommand,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + '_>> {
+        Box::pin(subcmd.parse(&self.db_dir))
+    }
+
+    async fn update(&self, date: &str, idx: u8, subcmd: UpdateSubCmd) -> Result<(), Error> {
+        self.run(SubCommand::Update {
+            old_date: command_date(date)?,
+            idx,
+            subcmd,
+        })
+        .await
+    }
+
+    /// Applies `body` to a copy of the task, so that a patch failing in any of its fields fails
+    /// before the others are written.
+    async fn check_update(&self, date: &str, idx: u8, body: &UpdateBody) -> Result<(), Error> {
+        let date = get_date(date)?;
+        let mut task = Schedule::read(&self.db_dir, date)
+            .await?
+            .tasks
+            .remove(&idx)
+            .ok_or(Error::Idx)?;
+        if let Some(time) = &body.time {
+            task.time =
+                TaskTime::from_str_in(&formats().canonical_time(time), &date, task.tz.as_ref())?;
+        }
+        if let Some(priority) = body.priority.as_deref().filter(|p| !p.is_empty()) {
+            Priority::from_str(priority)?;
+        }
+        if let Some(due) = body.due.as_deref().filter(|due| !due.is_empty()) {
+            Deadline::from_str(due)?;
+        }
+        if let Some(new_date) = &body.date {
+            task.time
+                .change_date(&get_date(new_date)?, task.tz.as_ref())?;
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        let method = req.method().clone();
+        let path = req.uri().path().to_string();
+        let query = parse_query(req.uri().query())?;
+        let body = body::to_bytes(req.into_body()).await?;
+
+        let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+        let task = |date: &str, idx: &str| -> Result<(String, u8), Error> {
+            get_date(date)?;
+            Ok((date.to_string(), idx.parse().map_err(|_| Error::Idx)?))
+        };
+
+        match (method.as_str(), segments.as_slice()) {
+            ("GET", ["schedules"]) => match (query.get("from"), query.get("to")) {
+                (None, None) => {
+                    self.schedule(query.get("date").map(String::as_str), StatusCode::OK)
+                        .await
+                }
+                (from, to) => {
+                    let from = from.map(|from| get_date(from)).transpose()?;
+                    let to = to.map(|to| get_date(to)).transpose()?;
+                    let mut schedules = Vec::new();
+                    for date in Schedule::stored_dates(&self.db_dir).await? {
+                        if from.map(|from| from <= date) != Some(false)
+                            && to.map(|to| date <= to) != Some(false)
+                        {
+                            schedules.push(Schedule::read(&self.db_dir, date).await?);
+                        }
+                    }
+                    let schedules: Vec<_> = schedules.iter().map(ScheduleJson::new).collect();
+                    json(StatusCode::OK, &schedules)
+                }
+            },
+            ("POST", ["tasks"]) => {
+                let body: AddBody = serde_json::from_slice(&body)?;
+                let date = body.date.clone();
+                self.run(SubCommand::Add {
+                    date: body.date.as_deref().map(command_date).transpose()?,
+                    time: body.time,
+                    pomodoro: body.pomodoro,
+                    priority: body.priority,
+                    after: body.after,
+                    due: body.due.as_deref().map(command_deadline).transpose()?,
+                    tz: body.tz,
+                    description: body.description,
+                })
+                .await?;
+                self.schedule(date.as_deref(), StatusCode::CREATED).await
+            }
+            ("PATCH", ["tasks", date, idx]) => {
+                let (date, idx) = task(date, idx)?;
+                let body: UpdateBody = parse_body(&body)?;
+                self.check_update(&date, idx, &body).await?;
+                if let Some(time) = body.time {
+                    self.update(&date, idx, UpdateSubCmd::Time { time }).await?;
+                }
+                if let Some(desc) = body.description {
+                    self.update(&date, idx, UpdateSubCmd::Description { desc })
+                        .await?;
+                }
+                if let Some(priority) = body.priority {
+                    let priority = Some(priority).filter(|priority| !priority.is_empty());
+                    self.update(&date, idx, UpdateSubCmd::Priority { priority })
+                        .await?;
+                }
+                if let Some(due) = body.due {
+                    let due = Some(due)
+                        .filter(|due| !due.is_empty())
+                        .map(|due| command_deadline(&due))
+                        .transpose()?;
+                    self.update(&date, idx, UpdateSubCmd::Due { due }).await?;
+                }
+                // Moving the task changes its index, so it is done last.
+                match body.date {
+                    Some(new_date) => {
+                        self.update(
+                            &date,
+                            idx,
+                            UpdateSubCmd::Date {
+                                date: command_date(&new_date)?,
+                            },
+                        )
+                        .await?;
+                        self.schedule(Some(&new_date), StatusCode::OK).await
+                    }
+                    None => self.schedule(Some(&date), StatusCode::OK).await,
+                }
+            }
+            ("DELETE", ["tasks", date, idx]) => {
+                let (date, idx) = task(date, idx)?;
+                self.run(SubCommand::Remove {
+                    date: command_date(&date)?,
+                    idx,
+                })
+                .await?;
+                self.schedule(Some(&date), StatusCode::OK).await
+            }
+            ("POST", ["tasks", date, idx, "toggle"]) => {
+                let (date, idx) = task(date, idx)?;
+                let body: ToggleBody = parse_body(&body)?;
+                let finished = Schedule::read(&self.db_dir, get_date(&date)?)
+                    .await?
+                    .tasks
+                    .get(&idx)
+                    .map(|task: &Task| task.finished)
+                    .ok_or(Error::Idx)?;
+                let subcmd = if finished {
+                    UpdateSubCmd::NotDone
+                } else {
+                    UpdateSubCmd::Done { force: body.force }
+                };
+                self.update(&date, idx, subcmd).await?;
+                self.schedule(Some(&date), StatusCode::OK).await
+            }
+            ("POST", ["tasks", date, idx, "pomodoro"]) => {
+                let (date, idx) = task(date, idx)?;
+                let body: PomodoroBody = parse_body(&body)?;
+                let update = match (body.remove, body.total, body.done) {
+                    (true, _, _) => PomodoroUpdate::Remove,
+                    (false, Some(total), _) => PomodoroUpdate::New { total },
+                    (false, None, Some(done)) => PomodoroUpdate::Done { done },
+                    (false, None, None) => {
+                        let pomodoro = Schedule::read(&self.db_dir, get_date(&date)?)
+                            .await?
+                            .tasks
+                            .get(&idx)
+                            .ok_or(Error::Idx)?
+                            .pomodoro;
+                        match pomodoro {
+                            Some((_, done)) => PomodoroUpdate::Done {
+                                done: done
+                                    .checked_add(1)
+                                    .ok_or(Error::Parse("too many pomodoro cycles"))?,
+                            },
+                            None => return Err(Error::Parse("task has no pomodoro")),
+                        }
+                    }
+                };
+                self.update(&date, idx, UpdateSubCmd::Pomodoro(update))
+                    .await?;
+                self.schedule(Some(&date), StatusCode::OK).await
+            }
+            _ => Ok(json_error(
+                StatusCode::NOT_FOUND,
+                format!("no route for {}", path),
+            )),
+        }
+    }
+}
+
+mod test {
+    #[tokio::test]
+    async fn test_api() {
+        use std::env;
+
+        use hyper::{body, Body, Request, StatusCode};
+        use serde_json::Value;
+        use tokio::{fs, sync::Mutex};
+
+        use super::{parse_query, Api};
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-http-{}", std::process::id()));
+        fs::create_dir_all(&db_dir).await.unwrap();
+        let api = Api {
+            db_dir: db_dir.clone(),
+            token: Some("secret".to_string()),
+            lock: Mutex::new(()),
+        };
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", "Bearer secret")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let respond = |req| async {
+            let response = api.respond(req).await;
+            let status = response.status();
+            let body = body::to_bytes(response.into_body()).await.unwrap();
+            (status, serde_json::from_slice::<Value>(&body).unwrap())
+        };
+
+        assert_eq!(
+            parse_query(Some("date=19%2D10-2026&q=a+b%20c&flag")).unwrap(),
+            vec![
+                ("date".to_string(), "19-10-2026".to_string()),
+                ("q".to_string(), "a b c".to_string()),
+                ("flag".to_string(), String::new()),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert!(parse_query(Some("date=%2")).is_err());
+
+        let unauthorized = Request::builder()
+            .uri("/schedules")
+            .header("Authorization", "Bearer secreT")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(respond(unauthorized).await.0, StatusCode::UNAUTHORIZED);
+
+        // Reading a date without a schedule does not create its file.
+        let (status, schedule) = respond(request("GET", "/schedules?date=20%2D10-2026", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(schedule["date"], "20-10-2026");
+        assert_eq!(schedule["tasks"], Value::Array(Vec::new()));
+        assert!(!db_dir.join("2026/10/20").exists());
+
+        let (status, schedule) = respond(request(
+            "POST",
+            "/tasks",
+            r#"{"date":"19-10-2026","time":"9:00","description":"write report"}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(schedule["tasks"][0]["description"], "write report");
+        assert_eq!(schedule["tasks"][0]["pomodoro"], Value::Null);
+
+        let (status, error) = respond(request(
+            "POST",
+            "/tasks/19-10-2026/1/pomodoro",
+            r#"{"done":1}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error["error"],
+            "error: parsing error : task has no pomodoro"
+        );
+        let (status, _) = respond(request("POST", "/tasks/19-10-2026/1/pomodoro", "")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = respond(request(
+            "POST",
+            "/tasks/19-10-2026/1/pomodoro",
+            r#"{"total":4}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = respond(request(
+            "POST",
+            "/tasks/19-10-2026/1/pomodoro",
+            r#"{"done":255}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = respond(request("POST", "/tasks/19-10-2026/1/pomodoro", "")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, schedule) = respond(request("POST", "/tasks/19-10-2026/1/toggle", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(schedule["tasks"][0]["finished"], true);
+        assert_eq!(
+            schedule["tasks"][0]["pomodoro"],
+            serde_json::json!({"total": 4, "done": 255})
+        );
+
+        // A patch failing in one field changes none of the others, nor moves the task.
+        let (status, _) = respond(request(
+            "POST",
+            "/tasks",
+            r#"{"date":"28-3-2026","time":"2:30","tz":"Europe/Paris","description":"backup"}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = respond(request(
+            "PATCH",
+            "/tasks/28-3-2026/1",
+            r#"{"time":"3:30","priority":"urgent"}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = respond(request(
+            "PATCH",
+            "/tasks/28-3-2026/1",
+            r#"{"description":"nightly backup","date":"29-3-2026"}"#,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, schedule) = respond(request("GET", "/schedules?date=28-3-2026", "")).await;
+        assert_eq!(schedule["tasks"][0]["description"], "backup");
+        assert_eq!(schedule["tasks"][0]["time"], "2:30");
+        assert_eq!(schedule["tasks"][0]["priority"], Value::Null);
+        let (_, schedule) = respond(request("GET", "/schedules?date=29-3-2026", "")).await;
+        assert_eq!(schedule["tasks"], Value::Array(Vec::new()));
+
+        let (status, _) = respond(request("DELETE", "/tasks/19-10-2026/2", "")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+mod caldav;
+mod http;
+
+pub use caldav::*;
+pub use http::*;
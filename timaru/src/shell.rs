@@ -0,0 +1,394 @@
+//! Interactive shell, started by `timaru shell`.
+//!
+//! Each line is read as the arguments of `timaru`, run against a current date, today's at first:
+//! `add` adds to it unless given `--date`, `list` lists it unless given a date, and `remove`,
+//! `update` and `start` work on its tasks when given an index instead of a date. The subcommands
+//! of `update` may also be given directly, as in `done 3`. The shell has commands of its own as
+//! well, see `SHELL_HELP`.
+//!
+//! Lines are kept in `shell_history` in the config directory, tab completes subcommands, dates and
+//! the tasks of the current date, and schedules are kept in memory between commands.
+
+use std::{
+    iter,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Date, Duration, Local, TimeZone};
+use clap::{Clap, IntoApp};
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+use tokio::{runtime::Handle, task};
+
+use crate::{
+    cli::{Opts, SubCommand},
+    complete,
+    error::Error,
+    format::formats,
+    schedule,
+    setup::config_dir,
+};
+
+const SHELL_HELP: &str = "\
+SHELL COMMANDS:
+    date [date|today|+N|-N]    Show or change the current date
+    exit, quit                 Leave the shell
+    help                       Print this message";
+
+/// Commands which take a date followed by the index of a task.
+const TASK_COMMANDS: [&str; 3] = ["remove", "update", "start"];
+
+/// Splits a line in words like a shell does, keeping the spaces of words in quotes and of ones
+/// escaped with `\`.
+fn split_words(line: &str) -> Result<Vec<String>, Error> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(c) = chars.next() {
+                    word.get_or_insert_with(String::new).push(c);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::Parse("unclosed quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Whether `word` is a subcommand of `timaru update`, which is not a subcommand of `timaru`.
+fn is_update_subcommand(word: &str) -> bool {
+    let app = Opts::into_app();
+    app.find_subcommand(word).is_none()
+        && app
+            .find_subcommand("update")
+            .and_then(|update| update.find_subcommand(word))
+            .is_some()
+}
+
+/// The arguments of `timaru` meant by `words` on the current `date`.
+fn expand(words: &[String], date: &str) -> Vec<String> {
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Vec::new(),
+    };
+    let is_idx = |word: Option<&String>| word.and_then(|word| word.parse::<u8>().ok()).is_some();
+    let mut expanded = vec![command.to_string()];
+
+    match command {
+        "add"
+            if !args
+                .iter()
+                .any(|arg| arg == "-d" || arg.starts_with("--date")) =>
+        {
+            expanded.extend(vec!["--date".to_string(), date.to_string()]);
+            expanded.extend_from_slice(args);
+        }
+        "list" => {
+            // The only option is `--tz`, which takes a value.
+            let mut positionals = args.iter().filter(|arg| !arg.starts_with('-')).count();
+            if args.iter().any(|arg| arg == "--tz") {
+                positionals = positionals.saturating_sub(1);
+            }
+            if positionals == 0 {
+                expanded.push(date.to_string());
+            }
+            expanded.extend_from_slice(args);
+        }
+        command if TASK_COMMANDS.contains(&command) && is_idx(args.first()) => {
+            expanded.push(date.to_string());
+            expanded.extend_from_slice(args);
+        }
+        command if is_update_subcommand(command) && is_idx(args.first()) => {
+            expanded = vec!["update".to_string(), date.to_string(), args[0].clone()];
+            expanded.push(command.to_string());
+            expanded.extend_from_slice(&args[1..]);
+        }
+        _ => expanded.extend_from_slice(args),
+    }
+
+    expanded
+}
+
+/// Completes lines of the shell, with the completions of `complete` on their expansion.
+struct ShellHelper {
+    db_dir: PathBuf,
+    /// The current date, in the date format.
+    date: String,
+}
+
+impl ShellHelper {
+    fn candidates(&self, words: Vec<String>) -> Vec<(String, String)> {
+        let word = match words.last() {
+            Some(word) => word.clone(),
+            None => return Vec::new(),
+        };
+
+        if words.len() == 1 {
+            let app = Opts::into_app();
+            let update = app.find_subcommand("update");
+            let shell_commands = ["date", "exit", "help", "quit"];
+            return app
+                .get_subcommands()
+                .chain(
+                    update
+                        .into_iter()
+                        .flat_map(|update| update.get_subcommands()),
+                )
+                .map(|subcommand| subcommand.get_name())
+                .chain(shell_commands.iter().copied())
+                .filter(|name| name.starts_with(word.as_str()))
+                .map(|name| (name.to_string(), String::new()))
+                .collect();
+        }
+
+        // The date is inserted before the words given, so that the last one is still the word
+        // completed.
+        let mut words = expand(&words, &self.date);
+        words.pop();
+        match words.as_slice() {
+            // The date of the shell is expected as the one of `list`.
+            [command] if command == "date" => words = vec!["list".to_string()],
+            // The index of a task of the current date is expected right after these commands.
+            [command] if TASK_COMMANDS.contains(&command.as_str()) => words.push(self.date.clone()),
+            [command] if is_update_subcommand(command) => {
+                words = vec!["update".to_string(), self.date.clone()]
+            }
+            _ => {}
+        }
+        words.push(word.clone());
+
+        Handle::current()
+            .block_on(complete::candidates(&self.db_dir, &words))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(value, _)| value.starts_with(word.as_str()))
+            .collect()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let mut words: Vec<_> = line.split_whitespace().map(str::to_string).collect();
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            words.push(String::new());
+        }
+        let start = pos - words.last().map_or(0, String::len);
+
+        let pairs = self
+            .candidates(words)
+            .into_iter()
+            .map(|(value, description)| Pair {
+                display: if description.is_empty() {
+                    value.clone()
+                } else {
+                    format!("{}  {}", value, description)
+                },
+                replacement: value,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// `date` moved by `days`, or `None` if that is past the dates which can be represented.
+fn add_days(date: &Date<Local>, days: i32) -> Option<Date<Local>> {
+    let date = date
+        .naive_local()
+        .checked_add_signed(Duration::days(days.into()))?;
+    Local.from_local_date(&date).earliest()
+}
+
+/// Runs `words` if they are a command of the shell rather than of `timaru`, which may change the
+/// current `date`. Returns whether the shell should be left, or `None` if they are not one.
+fn shell_command(words: &[String], date: &mut Date<Local>) -> Result<Option<bool>, Error> {
+    match words {
+        [command] if command == "exit" || command == "quit" => Ok(Some(true)),
+        [command] if command == "help" => {
+            Opts::into_app().print_help()?;
+            println!("\n\n{}", SHELL_HELP);
+            Ok(Some(false))
+        }
+        [command] if command == "date" => {
+            println!("{}", formats().date(date.naive_local()));
+            Ok(Some(false))
+        }
+        [command, arg] if command == "date" => {
+            *date = match arg.as_str() {
+                "today" => Local::today(),
+                arg if arg.starts_with('+') || arg.starts_with('-') => match arg.parse::<i32>() {
+                    Ok(days) => add_days(date, days).ok_or(Error::Parse("date out of range"))?,
+                    Err(_) => return Err(Error::Parse("days should be a number, as in +1")),
+                },
+                arg => formats().parse_date(arg)?,
+            };
+            Ok(Some(false))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Runs `line`, returning whether the shell should be left.
+async fn run_line(db_dir: &Path, line: &str, date: &mut Date<Local>) -> Result<bool, Error> {
+    let words = split_words(line)?;
+    if words.is_empty() {
+        return Ok(false);
+    }
+    if let Some(exit) = shell_command(&words, date)? {
+        return Ok(exit);
+    }
+
+    let words = expand(&words, &formats().date(date.naive_local()));
+    let opts = match Opts::try_parse_from(iter::once("timaru".to_string()).chain(words)) {
+        Ok(opts) => opts,
+        Err(err) => {
+            if err.use_stderr() {
+                eprintln!("{}", err);
+            } else {
+                println!("{}", err);
+            }
+            return Ok(false);
+        }
+    };
+
+    match opts.subcmd {
+        Some(SubCommand::Shell) => println!("already in the shell"),
+        // Boxed, as the shell is itself run by a subcommand.
+        Some(subcmd) => Box::pin(subcmd.parse(db_dir)).await?,
+        None => {}
+    }
+    Ok(false)
+}
+
+pub async fn run(db_dir: &Path) -> Result<(), Error> {
+    schedule::enable_cache();
+    let history = config_dir().await?.join("shell_history");
+    let mut date = Local::today();
+
+    let mut editor = Editor::new();
+    editor.set_helper(Some(ShellHelper {
+        db_dir: db_dir.to_path_buf(),
+        date: formats().date(date.naive_local()),
+    }));
+    // There is no history the first time.
+    let _ = editor.load_history(&history);
+
+    loop {
+        let date_string = formats().date(date.naive_local());
+        if let Some(helper) = editor.helper_mut() {
+            helper.date = date_string.clone();
+        }
+
+        let prompt = format!("timaru {}> ", date_string);
+        // Completing blocks on the runtime, which can only be done outside of it.
+        let line = match task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(Error::Shell(err.to_string())),
+        };
+        editor.add_history_entry(line.as_str());
+
+        match run_line(db_dir, &line, &mut date).await {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    editor
+        .save_history(&history)
+        .map_err(|err| Error::Shell(err.to_string()))
+}
+
+mod test {
+    #[test]
+    fn test_expand() {
+        use super::{expand, split_words};
+
+        let expand = |line: &str| expand(&split_words(line).unwrap(), "19-10-2026").join(" ");
+
+        assert_eq!(
+            split_words(r#"add "call mum"  it\'s\ late"#).unwrap(),
+            vec!["add", "call mum", "it's late"]
+        );
+        assert!(split_words("add \"call mum").is_err());
+
+        assert_eq!(
+            expand("add -t 9:00 gym"),
+            "add --date 19-10-2026 -t 9:00 gym"
+        );
+        assert_eq!(expand("add -d 20-10-2026 gym"), "add -d 20-10-2026 gym");
+        assert_eq!(expand("list"), "list 19-10-2026");
+        assert_eq!(
+            expand("list --tz Asia/Tokyo"),
+            "list 19-10-2026 --tz Asia/Tokyo"
+        );
+        assert_eq!(expand("list 20-10-2026"), "list 20-10-2026");
+        assert_eq!(expand("remove 3"), "remove 19-10-2026 3");
+        assert_eq!(expand("remove 20-10-2026 3"), "remove 20-10-2026 3");
+        assert_eq!(expand("update 3 done"), "update 19-10-2026 3 done");
+        assert_eq!(expand("start 1"), "start 19-10-2026 1");
+        assert_eq!(expand("done 3 --force"), "update 19-10-2026 3 done --force");
+        assert_eq!(expand("priority 2 A"), "update 19-10-2026 2 priority A");
+        assert_eq!(expand("week"), "week");
+    }
+
+    #[test]
+    fn test_date_command() {
+        use chrono::{Local, TimeZone};
+
+        use super::shell_command;
+
+        let mut date = Local.ymd(2026, 10, 19);
+        let mut run = |line: &str| {
+            let words: Vec<_> = line.split(' ').map(str::to_string).collect();
+            shell_command(&words, &mut date).map(|exit| exit.is_some())
+        };
+        assert!(run("date +13").unwrap());
+        assert!(run("date -1").unwrap());
+        assert!(run("date +99999999999").is_err());
+        assert!(run("date +2147483647").is_err());
+        assert!(run("date -2147483648").is_err());
+        assert_eq!(date, Local.ymd(2026, 10, 31));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{Date, Datelike, Duration, Local};
+use serde::Serialize;
+
+use crate::{error::Error, format::formats, schedule::Schedule, task::TimeOfDay};
+
+/// Statistics of the schedules between two dates.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub days: Vec<DayStats>,
+    pub weeks: Vec<PeriodStats>,
+    pub months: Vec<PeriodStats>,
+    pub pomodoros_planned: u32,
+    pub pomodoros_done: u32,
+    /// Number of tasks in each `TimeOfDay` bucket, busiest first.
+    pub time_of_day: Vec<(String, u32)>,
+    /// Number of consecutive days, ending today, with at least one finished task.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayStats {
+    pub date: String,
+    pub tasks: u32,
+    pub finished: u32,
+    pub pomodoros_planned: u32,
+    pub pomodoros_done: u32,
+}
+
+/// Tasks finished out of total tasks in a week (`2026-W42`) or a month (`10-2026`).
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodStats {
+    pub period: String,
+    pub tasks: u32,
+    pub finished: u32,
+}
+
+impl Stats {
+    /// Collects statistics of the schedules stored in `db_dir` from `start_date` to `end_date`,
+    /// both inclusive. Days without a schedule count as days without tasks.
+    pub async fn collect(
+        db_dir: &Path,
+        start_date: Date<Local>,
+        end_date: Date<Local>,
+    ) -> Result<Stats, Error> {
+        let stored_dates = Schedule::stored_dates(db_dir).await?;
+        let formats = formats();
+        let mut days = Vec::new();
+        let mut weeks: BTreeMap<(i32, u32), PeriodStats> = BTreeMap::new();
+        let mut months: BTreeMap<(i32, u32), PeriodStats> = BTreeMap::new();
+        let mut time_of_day: BTreeMap<TimeOfDay, u32> = BTreeMap::new();
+        let (mut current_streak, mut longest_streak) = (0, 0);
+
+        let mut date = start_date;
+        while date <= end_date {
+            let mut day = DayStats {
+                date: formats.date(date.naive_local()),
+                tasks: 0,
+                finished: 0,
+                pomodoros_planned: 0,
+                pomodoros_done: 0,
+            };
+
+            // Only open dates which have a schedule, as opening creates the schedule file.
+            if stored_dates.contains(&date) {
+                let schedule = Schedule::open(db_dir, date).await?;
+                for task in schedule.tasks.values() {
+                    day.tasks += 1;
+                    day.finished += task.finished as u32;
+                    if let Some((total, done)) = task.pomodoro {
+                        day.pomodoros_planned += total as u32;
+                        day.pomodoros_done += done as u32;
+                    }
+                    if let Some(start) = task.time.start_time() {
+                        *time_of_day.entry(TimeOfDay::of(&start)).or_default() += 1;
+                    }
+                }
+            }
+
+            let week = date.iso_week();
+            let week = weeks
+                .entry((week.year(), week.week()))
+                .or_insert_with(|| PeriodStats {
+                    period: format!("{}-W{:02}", week.year(), week.week()),
+                    tasks: 0,
+                    finished: 0,
+                });
+            week.tasks += day.tasks;
+            week.finished += day.finished;
+
+            let month = months
+                .entry((date.year(), date.month()))
+                .or_insert_with(|| PeriodStats {
+                    period: formats.month(date.naive_local()),
+                    tasks: 0,
+                    finished: 0,
+                });
+            month.tasks += day.tasks;
+            month.finished += day.finished;
+
+            if day.finished > 0 {
+                current_streak += 1;
+                longest_streak = longest_streak.max(current_streak);
+            } else if date != end_date {
+                // The last day might still be in progress, so it does not break the streak.
+                current_streak = 0;
+            }
+
+            days.push(day);
+            date = date + Duration::days(1);
+        }
+
+        let mut time_of_day: Vec<_> = time_of_day
+            .into_iter()
+            .map(|(time, count)| (time.as_str().to_string(), count))
+            .collect();
+        time_of_day.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+        Ok(Stats {
+            pomodoros_planned: days.iter().map(|day| day.pomodoros_planned).sum(),
+            pomodoros_done: days.iter().map(|day| day.pomodoros_done).sum(),
+            days,
+            weeks: weeks.into_values().collect(),
+            months: months.into_values().collect(),
+            time_of_day,
+            current_streak,
+            longest_streak,
+        })
+    }
+
+    pub fn as_string(&self) -> String {
+        fn rate(finished: u32, tasks: u32) -> String {
+            match (finished * 100).checked_div(tasks) {
+                Some(percent) => format!("{}/{} ({}%)", finished, tasks, percent),
+                None => "-".to_string(),
+            }
+        }
+
+        let mut s = String::from("# Completion rate per day\n");
+        for day in self.days.iter() {
+            s += &format!("{:<12} {}\n", day.date, rate(day.finished, day.tasks));
+        }
+        s += "\n# Completion rate per week\n";
+        for week in self.weeks.iter() {
+            s += &format!("{:<12} {}\n", week.period, rate(week.finished, week.tasks));
+        }
+        s += "\n# Completion rate per month\n";
+        for month in self.months.iter() {
+            s += &format!(
+                "{:<12} {}\n",
+                month.period,
+                rate(month.finished, month.tasks)
+            );
+        }
+
+        s += &format!(
+            "\n# Pomodoros\n{} done of {} planned\n",
+            self.pomodoros_done, self.pomodoros_planned
+        );
+
+        s += "\n# Busiest times of day\n";
+        for (time, count) in self.time_of_day.iter() {
+            s += &format!("{:<12} {}\n", time, count);
+        }
+
+        s += &format!(
+            "\n# Streaks\ncurrent: {} days\nlongest: {} days\n",
+            self.current_streak, self.longest_streak
+        );
+
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn test_stats() {
+        use std::env;
+
+        use chrono::{Local, TimeZone};
+        use tokio::fs;
+
+        use super::Stats;
+        use crate::schedule::Schedule;
+
+        let db_dir = env::temp_dir().join(format!("timaru-test-stats-{}", std::process::id()));
+        fs::create_dir_all(&db_dir).await.unwrap();
+        for (date, tasks) in [
+            (
+                Local.ymd(2026, 10, 18),
+                "* [X] 9:00 (4, 2) {id:1} => standup",
+            ),
+            (
+                Local.ymd(2026, 10, 19),
+                "* [X] 9:30 {id:1} => review\n* [ ] evening {id:2} => read",
+            ),
+        ] {
+            let path = Schedule::path(&db_dir, &date);
+            fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+            let header = format!("# {} v3\n", date.format("%-d-%-m-%Y"));
+            fs::write(&path, header + tasks).await.unwrap();
+        }
+
+        // The 20th has no finished task yet, but as the last day it does not break the streak.
+        let stats = Stats::collect(&db_dir, Local.ymd(2026, 10, 17), Local.ymd(2026, 10, 20))
+            .await
+            .unwrap();
+        let days: Vec<_> = stats
+            .days
+            .iter()
+            .map(|day| (day.date.as_str(), day.tasks, day.finished))
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                ("17-10-2026", 0, 0),
+                ("18-10-2026", 1, 1),
+                ("19-10-2026", 2, 1),
+                ("20-10-2026", 0, 0),
+            ]
+        );
+        let weeks: Vec<_> = stats
+            .weeks
+            .iter()
+            .map(|week| (week.period.as_str(), week.tasks, week.finished))
+            .collect();
+        assert_eq!(weeks, vec![("2026-W42", 1, 1), ("2026-W43", 2, 1)]);
+        assert_eq!(stats.months.len(), 1);
+        assert_eq!((stats.months[0].tasks, stats.months[0].finished), (3, 2));
+        assert_eq!((stats.pomodoros_planned, stats.pomodoros_done), (4, 2));
+        assert_eq!(
+            stats.time_of_day,
+            vec![("morning".to_string(), 2), ("evening".to_string(), 1)]
+        );
+        assert_eq!((stats.current_streak, stats.longest_streak), (2, 2));
+
+        let stats = Stats::collect(&db_dir, Local.ymd(2026, 10, 18), Local.ymd(2026, 10, 21))
+            .await
+            .unwrap();
+        assert_eq!((stats.current_streak, stats.longest_streak), (0, 2));
+
+        fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}
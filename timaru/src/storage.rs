@@ -0,0 +1,382 @@
+//! Encoding of schedule files, in the text or binary format of the database, and encrypted once
+//! the database is encrypted with `timaru encrypt`.
+//!
+//! The format is chosen with `timaru convert`, a database in the binary format having a `FORMAT`
+//! file holding `binary`. Files in either format are read whatever the format of the database.
+//!
+//! The `VERSION` file holds the version of the text format all the schedules were migrated to by
+//! `timaru migrate`, a database without one being of version 1. Older schedules are upgraded
+//! whenever they are read anyway, but a database of a newer version is refused.
+//!
+//! An encrypted database has an `ENCRYPTION` file holding the salt from which the key is derived
+//! with Argon2, followed by a known text encrypted with the key to check it. Each schedule file
+//! is then encrypted with XChaCha20-Poly1305, as a magic header, a random nonce and the
+//! ciphertext. Plain files are still read, and encrypted when written.
+//!
+//! The passphrase is read from the `keyfile` of the `[encryption]` config section, printed by
+//! its `passphrase_command`, taken from `$TIMARU_PASSPHRASE`, or asked for, in that order.
+//!
+//! The key and format are kept for each database read by `init`, so that schedules are written
+//! as their own database expects, e.g. plain ones in the copy of a remote being synced. Files in
+//! a directory which was not read by `init` are written in the plain text format.
+
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use tokio::fs;
+
+use crate::{config::Config, error::Error, parser::TEXT_VERSION, schedule::Schedule};
+
+const MAGIC: &[u8] = b"TIMARU-ENC\x01";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+/// Encrypted in the `ENCRYPTION` file to check the key.
+const CHECK: &[u8] = b"timaru";
+
+/// How the schedules of a database are stored.
+#[derive(Clone, Copy, Default)]
+struct Database {
+    /// The key of the database, set once it is unlocked.
+    key: Option<Key>,
+    /// Whether schedules are written in the binary format.
+    binary: bool,
+}
+
+/// The databases read by `init`, by directory.
+static DATABASES: Mutex<Option<HashMap<PathBuf, Database>>> = Mutex::new(None);
+
+/// The database the file at `path` belongs to.
+fn database(path: &Path) -> Database {
+    let databases = DATABASES.lock().unwrap();
+    let databases = match databases.as_ref() {
+        Some(databases) => databases,
+        None => return Database::default(),
+    };
+    path.ancestors()
+        .find_map(|dir| databases.get(dir))
+        .copied()
+        .unwrap_or_default()
+}
+
+fn update_database(db_dir: &Path, update: impl FnOnce(&mut Database)) {
+    let mut databases = DATABASES.lock().unwrap();
+    update(
+        databases
+            .get_or_insert_with(HashMap::new)
+            .entry(db_dir.to_path_buf())
+            .or_default(),
+    );
+}
+
+pub(crate) fn set_key(db_dir: &Path, key: Option<Key>) {
+    update_database(db_dir, |database| database.key = key);
+}
+
+fn set_binary(db_dir: &Path, binary: bool) {
+    update_database(db_dir, |database| database.binary = binary);
+}
+
+fn format_file(db_dir: &Path) -> PathBuf {
+    db_dir.join("FORMAT")
+}
+
+fn version_file(db_dir: &Path) -> PathBuf {
+    db_dir.join("VERSION")
+}
+
+fn encryption_file(db_dir: &Path) -> PathBuf {
+    db_dir.join("ENCRYPTION")
+}
+
+/// Whether the database in `db_dir` is encrypted.
+pub fn is_encrypted(db_dir: &Path) -> bool {
+    encryption_file(db_dir).is_file()
+}
+
+/// Encodes `schedule` in the format of its database, encrypted if the database is unlocked.
+pub fn encode(schedule: &Schedule) -> Vec<u8> {
+    seal(&schedule.file, content(schedule))
+}
+
+/// The content of the file of `schedule` before encryption, in the format of its database.
+pub(crate) fn content(schedule: &Schedule) -> Vec<u8> {
+    if database(&schedule.file).binary {
+        schedule.as_bytes()
+    } else {
+        schedule.as_string().into_bytes()
+    }
+}
+
+/// Encrypts `bytes`, to be written to the file at `path`, if its database is unlocked.
+pub(crate) fn seal(path: &Path, bytes: Vec<u8>) -> Vec<u8> {
+    match database(path).key {
+        Some(key) => encrypt(&key, &bytes),
+        None => bytes,
+    }
+}
+
+/// Decrypts the content of the schedule file at `path` if it is encrypted.
+pub fn decode(path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(bytes);
+    }
+    match database(path).key {
+        Some(key) => decrypt(&key, &bytes).ok_or_else(|| Error::Decrypt(path.into())),
+        None => Err(Error::Decrypt(path.into())),
+    }
+}
+
+fn encrypt(key: &Key, plaintext: &[u8]) -> Vec<u8> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key)
+        .encrypt(&nonce, plaintext)
+        .expect("encryption of a schedule failed");
+    [MAGIC, nonce.as_slice(), &ciphertext].concat()
+}
+
+fn decrypt(key: &Key, bytes: &[u8]) -> Option<Vec<u8>> {
+    let bytes = bytes.strip_prefix(MAGIC)?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+pub(crate) fn derive_key(passphrase: &[u8], salt: &[u8]) -> Result<Key, Error> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|_| Error::Encryption("unable to derive the key"))?;
+    Ok(key)
+}
+
+/// Reads a passphrase from the terminal without echoing it.
+fn prompt(text: &str) -> Result<String, Error> {
+    eprint!("{}", text);
+    io::stderr().flush()?;
+
+    enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let read = loop {
+        match event::read() {
+            Ok(Event::Key(KeyEvent { code, modifiers })) => match code {
+                KeyCode::Enter => break Ok(true),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break Ok(false),
+                KeyCode::Esc => break Ok(false),
+                KeyCode::Char(c) => passphrase.push(c),
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+
+    match read? {
+        true => Ok(passphrase),
+        false => Err(Error::Encryption("no passphrase given")),
+    }
+}
+
+/// The passphrase of the database, from the first configured source. A passphrase asked for is
+/// asked twice if `confirm` is set.
+async fn passphrase(confirm: bool) -> Result<Vec<u8>, Error> {
+    let config = Config::load().await?.encryption;
+
+    if let Some(keyfile) = config.keyfile {
+        return Ok(fs::read(keyfile).await?);
+    }
+    if let Some(command) = config.passphrase_command {
+        let output = Command::new("sh").arg("-c").arg(&command).output()?;
+        if !output.status.success() {
+            return Err(Error::Encryption("passphrase command failed"));
+        }
+        let passphrase = String::from_utf8_lossy(&output.stdout);
+        return Ok(passphrase.trim_end_matches('\n').as_bytes().to_vec());
+    }
+    if let Ok(passphrase) = env::var("TIMARU_PASSPHRASE") {
+        return Ok(passphrase.into_bytes());
+    }
+
+    let passphrase = prompt("passphrase: ")?;
+    if confirm && prompt("passphrase again: ")? != passphrase {
+        return Err(Error::Encryption("passphrases do not match"));
+    }
+    Ok(passphrase.into_bytes())
+}
+
+/// Version of the database in `db_dir`.
+pub async fn version(db_dir: &Path) -> Result<u32, Error> {
+    match fs::read_to_string(version_file(db_dir)).await {
+        Ok(version) => version
+            .trim()
+            .parse()
+            .map_err(|_| Error::Parse("invalid VERSION file")),
+        Err(_) => Ok(1),
+    }
+}
+
+/// Checks the version of the database in `db_dir` and reads its format, and unlocks it if it is
+/// encrypted, so that schedules can be read and written.
+pub async fn init(db_dir: &Path) -> Result<(), Error> {
+    init_locked(db_dir).await?;
+    unlock(db_dir).await
+}
+
+/// Same as `init`, without unlocking an encrypted database, whose schedules then can't be read.
+pub async fn init_locked(db_dir: &Path) -> Result<(), Error> {
+    let version = version(db_dir).await?;
+    if version > TEXT_VERSION {
+        return Err(Error::NewerVersion(version));
+    }
+    let binary = match fs::read_to_string(format_file(db_dir)).await {
+        Ok(format) => format.trim() == "binary",
+        Err(_) => false,
+    };
+    set_binary(db_dir, binary);
+    Ok(())
+}
+
+async fn unlock(db_dir: &Path) -> Result<(), Error> {
+    if !is_encrypted(db_dir) {
+        return Ok(());
+    }
+
+    let content = fs::read(encryption_file(db_dir)).await?;
+    if content.len() < SALT_LEN {
+        return Err(Error::Encryption("invalid ENCRYPTION file"));
+    }
+    let (salt, check) = content.split_at(SALT_LEN);
+    let key = derive_key(&passphrase(false).await?, salt)?;
+    if decrypt(&key, check).as_deref() != Some(CHECK) {
+        return Err(Error::Encryption("wrong passphrase or key"));
+    }
+
+    set_key(db_dir, Some(key));
+    Ok(())
+}
+
+/// Writes schedule files, encrypted with the key of their database if any.
+async fn write_schedules(contents: Vec<(PathBuf, Vec<u8>)>) -> Result<(), Error> {
+    for (path, content) in contents {
+        fs::write(&path, seal(&path, content)).await?;
+    }
+    Ok(())
+}
+
+/// Reads every schedule file of `db_dir`, decrypted.
+async fn read_schedules(db_dir: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>, Error> {
+    let mut contents = Vec::new();
+    for date in Schedule::stored_dates(db_dir).await? {
+        let path = Schedule::path(db_dir, &date);
+        let content = decode(&path, fs::read(&path).await?)?;
+        contents.push((path, content));
+    }
+    Ok(contents)
+}
+
+/// Encrypts the database in `db_dir` with a new passphrase.
+pub async fn encrypt_db(db_dir: &Path) -> Result<(), Error> {
+    if is_encrypted(db_dir) {
+        return Err(Error::Encryption("the database is already encrypted"));
+    }
+    let contents = read_schedules(db_dir).await?;
+
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(&passphrase(true).await?, &salt)?;
+    fs::write(
+        encryption_file(db_dir),
+        [&salt[..], &encrypt(&key, CHECK)].concat(),
+    )
+    .await?;
+
+    set_key(db_dir, Some(key));
+    write_schedules(contents).await
+}
+
+/// Decrypts the database in `db_dir`, which has to be unlocked.
+pub async fn decrypt_db(db_dir: &Path) -> Result<(), Error> {
+    if !is_encrypted(db_dir) {
+        return Err(Error::Encryption("the database is not encrypted"));
+    }
+    let contents = read_schedules(db_dir).await?;
+
+    set_key(db_dir, None);
+    write_schedules(contents).await?;
+    fs::remove_file(encryption_file(db_dir)).await?;
+    Ok(())
+}
+
+/// Converts every schedule of `db_dir` to the binary format if `binary` is set, or else to the
+/// text format, and writes new schedules in that format.
+pub async fn convert(db_dir: &Path, binary: bool) -> Result<(), Error> {
+    set_binary(db_dir, binary);
+    for date in Schedule::stored_dates(db_dir).await? {
+        Schedule::open(db_dir, date).await?.sync().await?;
+    }
+
+    if binary {
+        fs::write(format_file(db_dir), "binary\n").await?;
+    } else if fs::metadata(format_file(db_dir)).await.is_ok() {
+        fs::remove_file(format_file(db_dir)).await?;
+    }
+    Ok(())
+}
+
+/// Upgrades every schedule of `db_dir` to the current version of the text format, and records
+/// it as the version of the database. Returns the version the database was of.
+pub async fn migrate(db_dir: &Path) -> Result<u32, Error> {
+    let version = version(db_dir).await?;
+    for date in Schedule::stored_dates(db_dir).await? {
+        Schedule::open(db_dir, date).await?.sync().await?;
+    }
+
+    fs::write(version_file(db_dir), format!("{}\n", TEXT_VERSION)).await?;
+    Ok(version)
+}
+
+mod test {
+    #[test]
+    fn test_encryption_round_trip() {
+        use std::path::Path;
+
+        use super::{decode, decrypt, derive_key, encrypt, CHECK};
+
+        let key = derive_key(b"correct horse", b"0123456789abcdef").unwrap();
+        let other_key = derive_key(b"wrong horse", b"0123456789abcdef").unwrap();
+        let encrypted = encrypt(&key, CHECK);
+
+        assert_ne!(&encrypted[..], CHECK);
+        assert_eq!(decrypt(&key, &encrypted).as_deref(), Some(CHECK));
+        assert_eq!(decrypt(&other_key, &encrypted), None);
+        // Plain files are read as they are, encrypted ones fail without the key.
+        assert_eq!(
+            decode(Path::new("1"), b"# 1-1-2026".to_vec()).unwrap(),
+            b"# 1-1-2026"
+        );
+        assert!(decode(Path::new("1"), encrypted).is_err());
+    }
+}
@@ -1,15 +1,81 @@
-use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
+use std::{
+    cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{self, AtomicU64},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use chrono::{Date, DateTime, Local, NaiveTime, Timelike};
+use chrono::{Date, DateTime, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::error::Error;
 
 #[derive(Debug, Clone)]
 pub struct Task {
+    /// Identifier of the task which stays the same across edits and date changes.
+    pub id: String,
     pub time: TaskTime,
     pub description: String,
     pub pomodoro: Option<(u8, u8)>,
+    pub priority: Option<Priority>,
+    pub deadline: Option<Deadline>,
+    /// Ids of the tasks which have to be finished before this task can be started.
+    pub dependencies: Vec<String>,
+    pub subtasks: Vec<Subtask>,
+    /// Intervals during which the task was worked on, as recorded by `timaru start`/`stop`.
+    pub intervals: Vec<WorkInterval>,
+    pub notes: Vec<String>,
+    pub finished: bool,
+    /// Date on which the task was finished, if it is.
+    pub done: Option<Date<Local>>,
+    /// Timezone in which the times of the task are written. Tasks without one follow the local
+    /// time of whoever reads them.
+    pub tz: Option<Zone>,
+    /// When the task was last changed, used by `timaru sync` to merge copies of the database.
+    /// Set when the schedule of the task is written.
+    pub modified: Option<DateTime<Local>>,
+}
+
+/// The date, and optionally the time, by which a task is due. Unlike `TaskTime`, which is when
+/// the task is planned to be worked on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadline {
+    pub date: Date<Local>,
+    pub time: Option<NaiveTime>,
+}
+
+/// An IANA timezone like `Europe/Paris`, or a fixed offset from UTC like `+02:00`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Named(Tz),
+    Offset(FixedOffset),
+}
+
+/// A period of time spent working on a task. The interval is still running if it has no end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkInterval {
+    pub start: DateTime<Local>,
+    pub end: Option<DateTime<Local>>,
+}
+
+/// A checklist item of a `Task`, stored as an indented line below the task in schedule files.
+#[derive(Debug, Clone)]
+pub struct Subtask {
+    pub description: String,
     pub finished: bool,
 }
 
+/// Priority of a task, `High` being the most urgent. Stored as `A`, `B` or `C` in schedule files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+    /// The priorities after `C` allowed by todo.txt, from `D` (0) to `Z` (22).
+    Lower(u8),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TaskTime {
     Precise {
@@ -41,18 +107,221 @@ pub enum TimeOfDay {
     Custom(String),
 }
 
+impl Task {
+    /// A new unfinished task with a new id and without any of the optional properties.
+    pub fn new(time: TaskTime, description: String) -> Task {
+        Task {
+            id: Task::new_id(),
+            time,
+            description,
+            pomodoro: None,
+            priority: None,
+            deadline: None,
+            dependencies: Vec::new(),
+            subtasks: Vec::new(),
+            intervals: Vec::new(),
+            notes: Vec::new(),
+            finished: false,
+            done: None,
+            tz: None,
+            modified: None,
+        }
+    }
+
+    /// Generates a new identifier for a task.
+    pub fn new_id() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u64(COUNTER.fetch_add(1, atomic::Ordering::Relaxed));
+
+        format!("{:08x}", hasher.finish() as u32)
+    }
+
+    /// Ordering used for listings: tasks are sorted by the start of their `TaskTime`, and tasks
+    /// starting at the same time (e.g. both in the `morning` block) are sorted by priority.
+    /// Tasks without a known start time or without a priority come last.
+    pub fn schedule_cmp(&self, other: &Self) -> Ordering {
+        let key = |task: &Task| {
+            let start = task.time.start_time();
+            (
+                start.is_none(),
+                start,
+                task.priority.is_none(),
+                task.priority,
+            )
+        };
+        key(self).cmp(&key(other))
+    }
+
+    /// Marks the task, along with all of its subtasks, as finished or not finished. Tasks
+    /// finished again keep the date on which they were first finished.
+    pub fn set_finished(&mut self, finished: bool) {
+        self.done = match finished {
+            true => self.done.or_else(|| Some(Local::today())),
+            false => None,
+        };
+        self.finished = finished;
+        for subtask in self.subtasks.iter_mut() {
+            subtask.finished = finished;
+        }
+    }
+
+    /// Whether the description, subtasks or notes of the task contain `pattern`, ignoring case.
+    pub fn matches(&self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        let contains = |s: &str| s.to_lowercase().contains(&pattern);
+
+        contains(&self.description)
+            || self
+                .subtasks
+                .iter()
+                .any(|subtask| contains(&subtask.description))
+            || self.notes.iter().any(|line| contains(line))
+    }
+
+    /// Whether the task is currently being worked on.
+    pub fn is_running(&self) -> bool {
+        self.intervals.iter().any(|interval| interval.end.is_none())
+    }
+
+    /// Total time spent working on the task, counting running intervals until `now`.
+    pub fn tracked_duration(&self, now: &DateTime<Local>) -> Duration {
+        self.intervals
+            .iter()
+            .fold(Duration::zero(), |total, interval| {
+                total + (interval.end.unwrap_or(*now) - interval.start)
+            })
+    }
+
+    /// A task with subtasks is finished only when all of its subtasks are finished.
+    pub fn derive_finished(&mut self) {
+        if !self.subtasks.is_empty() {
+            self.finished = self.subtasks.iter().all(|subtask| subtask.finished);
+        }
+    }
+}
+
+impl Deadline {
+    /// The moment the task is due, which is the end of the day if no time is given. A time
+    /// skipped by a change of the local clocks is taken as the same time an hour later.
+    pub fn as_datetime(&self) -> DateTime<Local> {
+        let time = self
+            .date
+            .naive_local()
+            .and_time(self.time.unwrap_or_else(|| NaiveTime::from_hms(23, 59, 59)));
+        Local
+            .from_local_datetime(&time)
+            .earliest()
+            .or_else(|| {
+                Local
+                    .from_local_datetime(&(time + Duration::hours(1)))
+                    .earliest()
+            })
+            .unwrap_or_else(|| Local.from_utc_datetime(&time))
+    }
+}
+
 impl TaskTime {
-    pub fn change_date(&mut self, date: &Date<Local>) {
+    /// The time at which the task starts, if it can be known.
+    pub fn start_time(&self) -> Option<NaiveTime> {
+        match self {
+            TaskTime::Precise { time } => Some(time.time()),
+            TaskTime::General { time } => time.to_time().map(|(start, _)| start),
+            TaskTime::Period { start, .. } => Some(start.time()),
+            TaskTime::GeneralPeriod { start, .. } => start.to_time().map(|(start, _)| start),
+        }
+    }
+
+    /// The planned length of the task, which is only known for `TaskTime::Period`.
+    pub fn planned_duration(&self) -> Option<Duration> {
+        match self {
+            TaskTime::Period { start, end } => Some(*end - *start),
+            _ => None,
+        }
+    }
+
+    /// Moves the task to `date`, keeping its times on the clocks of `zone`, or the local ones if
+    /// none is given.
+    pub fn change_date(&mut self, date: &Date<Local>, zone: Option<&Zone>) -> Result<(), Error> {
+        self.map_times(|time| {
+            let wall_clock = Zone::wall_clock_of(zone, time);
+            Zone::at_wall_clock_of(zone, &date.naive_local().and_time(wall_clock.time()))
+        })
+    }
+
+    /// Moves the times of the task from the clocks of `from` to the same times on the clocks of
+    /// `to`, the local clocks being used for the zone which is not given.
+    pub fn change_zone(&mut self, from: Option<&Zone>, to: Option<&Zone>) -> Result<(), Error> {
+        self.map_times(|time| Zone::at_wall_clock_of(to, &Zone::wall_clock_of(from, time)))
+    }
+
+    fn map_times(
+        &mut self,
+        f: impl Fn(&DateTime<Local>) -> Result<DateTime<Local>, Error>,
+    ) -> Result<(), Error> {
         match self {
             TaskTime::Period { start, end } => {
-                *start = date.and_hms(start.hour(), start.minute(), start.second());
-                *end = date.and_hms(end.hour(), end.minute(), end.second());
-            }
-            TaskTime::Precise { time } => {
-                *time = date.and_hms(time.hour(), time.minute(), time.second());
+                *start = f(start)?;
+                *end = f(end)?;
             }
+            TaskTime::Precise { time } => *time = f(time)?,
             _ => {}
         }
+        Ok(())
+    }
+}
+
+impl Zone {
+    /// The time shown by the clocks of the zone at `time`.
+    pub fn wall_clock(&self, time: &DateTime<Local>) -> NaiveDateTime {
+        match self {
+            Zone::Named(tz) => time.with_timezone(tz).naive_local(),
+            Zone::Offset(offset) => time.with_timezone(offset).naive_local(),
+        }
+    }
+
+    /// The moment at which the clocks of the zone show `time`, the earliest one if they show it
+    /// twice.
+    pub fn at_wall_clock(&self, time: &NaiveDateTime) -> Result<DateTime<Local>, Error> {
+        let moment = match self {
+            Zone::Named(tz) => tz
+                .from_local_datetime(time)
+                .earliest()
+                .map(|moment| moment.with_timezone(&Local)),
+            Zone::Offset(offset) => offset
+                .from_local_datetime(time)
+                .earliest()
+                .map(|moment| moment.with_timezone(&Local)),
+        };
+        moment.ok_or(Error::Parse("time does not exist in the timezone"))
+    }
+
+    /// Same as `wall_clock`, for the local clocks if no zone is given.
+    pub fn wall_clock_of(zone: Option<&Zone>, time: &DateTime<Local>) -> NaiveDateTime {
+        match zone {
+            Some(zone) => zone.wall_clock(time),
+            None => time.naive_local(),
+        }
+    }
+
+    /// Same as `at_wall_clock`, for the local clocks if no zone is given.
+    pub fn at_wall_clock_of(
+        zone: Option<&Zone>,
+        time: &NaiveDateTime,
+    ) -> Result<DateTime<Local>, Error> {
+        match zone {
+            Some(zone) => zone.at_wall_clock(time),
+            None => Local
+                .from_local_datetime(time)
+                .earliest()
+                .ok_or(Error::Parse("time does not exist in the local timezone")),
+        }
     }
 }
 
@@ -142,6 +411,25 @@ impl PartialOrd for TaskTime {
 }
 
 impl TimeOfDay {
+    /// The bucket `time` falls into, times before the morning being counted as `MidNight`.
+    pub fn of(time: &NaiveTime) -> TimeOfDay {
+        [
+            TimeOfDay::Morning,
+            TimeOfDay::Noon,
+            TimeOfDay::AfterNoon,
+            TimeOfDay::Evening,
+            TimeOfDay::Night,
+        ]
+        .iter()
+        .find(|time_of_day| match time_of_day.to_time() {
+            Some((start, Some(end))) => start <= *time && *time <= end,
+            Some((start, None)) => start == *time,
+            None => false,
+        })
+        .cloned()
+        .unwrap_or(TimeOfDay::MidNight)
+    }
+
     pub fn to_time(&self) -> Option<(NaiveTime, Option<NaiveTime>)> {
         match self {
             TimeOfDay::Morning => Some((
@@ -166,3 +454,75 @@ impl TimeOfDay {
         }
     }
 }
+
+mod test {
+    #[test]
+    fn test_change_zone() {
+        use std::str::FromStr;
+
+        use chrono::{NaiveDate, Timelike};
+
+        use super::{TaskTime, Zone};
+
+        let new_york = Zone::from_str("America/New_York").unwrap();
+        let paris = Zone::from_str("Europe/Paris").unwrap();
+        let day = NaiveDate::from_ymd(2026, 10, 19);
+        let mut time = TaskTime::Period {
+            start: new_york.at_wall_clock(&day.and_hms(9, 0, 0)).unwrap(),
+            end: new_york.at_wall_clock(&day.and_hms(10, 30, 0)).unwrap(),
+        };
+
+        time.change_zone(Some(&new_york), Some(&paris)).unwrap();
+        match time {
+            TaskTime::Period { start, end } => {
+                assert_eq!(paris.wall_clock(&start), day.and_hms(9, 0, 0));
+                assert_eq!(paris.wall_clock(&end), day.and_hms(10, 30, 0));
+                // 9:00 in Paris is 3:00 in New York.
+                assert_eq!(new_york.wall_clock(&start).hour(), 3);
+            }
+            _ => unreachable!(),
+        }
+
+        // 2:30 does not exist in Paris on the day clocks go forward.
+        let gap = NaiveDate::from_ymd(2026, 3, 29).and_hms(2, 30, 0);
+        let mut time = TaskTime::Precise {
+            time: new_york.at_wall_clock(&gap).unwrap(),
+        };
+        assert!(time.change_zone(Some(&new_york), Some(&paris)).is_err());
+    }
+
+    #[test]
+    fn test_tracked_duration() {
+        use chrono::{Duration, Local, TimeZone};
+
+        use super::{Task, TaskTime, WorkInterval};
+
+        let date = Local.ymd(2026, 10, 19);
+        let mut task = Task::new(
+            TaskTime::Precise {
+                time: date.and_hms(9, 0, 0),
+            },
+            "write report".to_string(),
+        );
+        let now = date.and_hms(12, 0, 0);
+        assert_eq!(task.tracked_duration(&now), Duration::zero());
+        assert!(!task.is_running());
+
+        task.intervals = vec![
+            WorkInterval {
+                start: date.and_hms(9, 0, 0),
+                end: Some(date.and_hms(9, 45, 30)),
+            },
+            WorkInterval {
+                start: date.and_hms(11, 30, 0),
+                end: None,
+            },
+        ];
+        // The running interval counts until now.
+        assert!(task.is_running());
+        assert_eq!(
+            task.tracked_duration(&now),
+            Duration::minutes(75) + Duration::seconds(30)
+        );
+    }
+}
@@ -1,25 +1,45 @@
-use chrono::Datelike;
+use std::collections::HashSet;
+
 use tui::{
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
     widgets::{Paragraph, Wrap},
 };
 
-use crate::{schedule::Schedule, task::Task};
+use crate::{
+    format::formats,
+    schedule::Schedule,
+    task::{Priority, Task},
+};
 
 impl Schedule {
-    pub fn as_widget_paragraph(&self) -> Paragraph {
+    /// The tasks shown in the TUI, which are only `Priority::High` tasks if `high_priority_only` is
+    /// set.
+    pub fn shown_tasks(&self, high_priority_only: bool) -> Vec<(&u8, &Task)> {
+        self.sorted_tasks()
+            .into_iter()
+            .filter(|(_, task)| !high_priority_only || task.priority == Some(Priority::High))
+            .collect()
+    }
+
+    /// Renders the schedule, showing only `Priority::High` tasks if `high_priority_only` is set.
+    /// Tasks whose ids are in `blocked` are dimmed, and the `selected`th shown task is highlighted.
+    pub fn as_widget_paragraph(
+        &self,
+        high_priority_only: bool,
+        blocked: &HashSet<String>,
+        selected: Option<usize>,
+    ) -> Paragraph<'_> {
         let mut text = vec![
             Spans::from(Span::raw(format!(
-                "# {}-{}-{}",
-                self.date.day(),
-                self.date.month(),
-                self.date.year()
+                "# {}",
+                formats().date(self.date.naive_local())
             ))),
             Spans::from(Span::raw("")),
         ];
 
-        for task in self.tasks.values() {
-            text.push(Spans::from(task.as_tui_span()));
+        for (i, (_, task)) in self.shown_tasks(high_priority_only).into_iter().enumerate() {
+            text.extend(task.as_tui_spans(blocked.contains(&task.id), selected == Some(i)));
         }
 
         Paragraph::new(text).wrap(Wrap { trim: true })
@@ -27,8 +47,41 @@ impl Schedule {
 }
 
 impl Task {
-    #[inline]
-    pub fn as_tui_span(&self) -> Span {
-        Span::raw(self.as_string())
+    /// Renders the task as a tree, the first line being the task itself followed by its subtasks.
+    pub fn as_tui_spans(&self, blocked: bool, selected: bool) -> Vec<Spans<'_>> {
+        let style = match self.priority {
+            Some(Priority::High) => Style::default().fg(Color::Red),
+            Some(Priority::Medium) => Style::default().fg(Color::Yellow),
+            Some(Priority::Low) | Some(Priority::Lower(_)) => Style::default().fg(Color::Blue),
+            None => Style::default(),
+        };
+        let style = if blocked {
+            style.add_modifier(Modifier::DIM)
+        } else {
+            style
+        };
+        let style = if selected {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        };
+
+        let mut spans = vec![Spans::from(Span::styled(
+            self.header_as_string_with(None, &formats()),
+            style,
+        ))];
+        for (i, subtask) in self.subtasks.iter().enumerate() {
+            let branch = if i + 1 == self.subtasks.len() {
+                "└─"
+            } else {
+                "├─"
+            };
+            spans.push(Spans::from(Span::raw(format!(
+                "  {} {}",
+                branch,
+                subtask.as_string()
+            ))));
+        }
+        spans
     }
 }
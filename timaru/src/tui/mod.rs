@@ -4,7 +4,7 @@ use std::{
     time,
 };
 
-use chrono::{Duration, Local};
+use chrono::{Date, Duration, Local};
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent},
     execute,
@@ -12,22 +12,26 @@ use crossterm::{
 };
 use tracing::info;
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    widgets::{Block, Borders, Row, Table},
-    Terminal,
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::Span,
+    widgets::{BarChart, Block, Borders, Gauge, Paragraph, Row, Sparkline, Table},
+    Frame, Terminal,
 };
 
-use crate::{error::Error, schedule::Schedule};
+use crate::{
+    editor, error::Error, parser::duration_as_string, schedule::Schedule, stats::Stats, task::Task,
+};
 
 mod format;
-pub use format::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TuiMode {
     Day,
     Week,
     Month,
+    Stats,
     Edit,
     Empty,
 }
@@ -38,6 +42,9 @@ pub struct TimaruTui {
     terminal: TermType,
     mode: TuiMode,
     db_dir: PathBuf,
+    high_priority_only: bool,
+    /// Index of the selected task among the tasks shown for today, whose notes `e` edits.
+    selected: usize,
 }
 
 macro_rules! __impl_change_mode {
@@ -63,6 +70,23 @@ macro_rules! __impl_change_mode {
                     if event::poll(time::Duration::from_millis(100))? {
                         match event::read()? {
                             $(gen_key!($key $val) => self.$f_name().await?,)+
+                            gen_key!(key 'e') => {
+                                self.edit_selected_notes().await?;
+                                self.change_mode(self.mode).await?;
+                            }
+                            gen_key!(key 'j') => {
+                                self.select(self.selected.saturating_add(1)).await?;
+                                self.change_mode(self.mode).await?;
+                            }
+                            gen_key!(key 'k') => {
+                                self.select(self.selected.saturating_sub(1)).await?;
+                                self.change_mode(self.mode).await?;
+                            }
+                            gen_key!(key 'p') => {
+                                self.high_priority_only = !self.high_priority_only;
+                                self.select(self.selected).await?;
+                                self.change_mode(self.mode).await?;
+                            }
                             gen_key!(key 'q') => break 'outer,
                             _ => {}
                         }
@@ -78,6 +102,38 @@ macro_rules! __impl_change_mode {
     }
 }
 
+/// The task being worked on, if any, with the time tracked on it so far.
+fn running_task(tasks: &[(Date<Local>, u8, Task)]) -> Option<String> {
+    tasks
+        .iter()
+        .find(|(_, _, task)| task.is_running())
+        .map(|(_, _, task)| {
+            format!(
+                "Working on: {} ({})",
+                task.description,
+                duration_as_string(&task.tracked_duration(&Local::now()))
+            )
+        })
+}
+
+/// Draws the running task on the last line of the frame, returning the area left for the mode.
+fn draw_running<B: Backend>(f: &mut Frame<B>, running: Option<String>) -> Rect {
+    let splits = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(if running.is_some() { 1 } else { 0 }),
+        ])
+        .split(f.size());
+    if let Some(running) = running {
+        f.render_widget(
+            Paragraph::new(Span::styled(running, Style::default().fg(Color::Green))),
+            splits[1],
+        );
+    }
+    splits[0]
+}
+
 macro_rules! gen_key {
     (key $val:literal) => {
         Event::Key(KeyEvent {
@@ -96,22 +152,60 @@ impl TimaruTui {
         // SAFETY: We won't be writing anything to the terminal outside the `TimaruTui` struct.
         Ok(TimaruTui {
             terminal: Terminal::new(CrosstermBackend::new(unsafe {
-                std::mem::transmute::<_, StdoutLock<'static>>(stdout.lock())
+                std::mem::transmute::<StdoutLock<'_>, StdoutLock<'static>>(stdout.lock())
             }))?,
             mode: TuiMode::Empty,
             db_dir,
+            high_priority_only: false,
+            selected: 0,
         })
     }
 
+    /// Selects the `selected`th task shown for today, or the last one if there are fewer tasks.
+    async fn select(&mut self, selected: usize) -> Result<(), Error> {
+        let schedule = Schedule::read(&self.db_dir, Local::today()).await?;
+        let shown = schedule.shown_tasks(self.high_priority_only).len();
+        self.selected = selected.min(shown.saturating_sub(1));
+        Ok(())
+    }
+
+    /// Opens the notes of the selected task in `$EDITOR`. If the editor fails, the notes are kept.
+    async fn edit_selected_notes(&mut self) -> Result<(), Error> {
+        let mut schedule = Schedule::open(&self.db_dir, Local::today()).await?;
+        let idx = match schedule
+            .shown_tasks(self.high_priority_only)
+            .get(self.selected)
+        {
+            Some((idx, _)) => **idx,
+            None => return Ok(()),
+        };
+        let task = schedule.tasks.get_mut(&idx).unwrap();
+
+        disable_raw_mode()?;
+        execute!(io::stdout(), LeaveAlternateScreen)?;
+        let edited = editor::edit(&task.notes.join("\n"));
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        enable_raw_mode()?;
+        self.terminal.clear()?;
+
+        if let Ok(notes) = edited {
+            task.notes = notes.trim_end().lines().map(str::to_string).collect();
+        }
+
+        Ok(())
+    }
+
     #[allow(dead_code)]
     async fn testing_stuff(&mut self) -> Result<(), Error> {
-        let schedules = Schedule::open_range(
+        let schedules = Schedule::read_range(
             &self.db_dir,
             Local::today(),
             Local::today() + Duration::days(7),
         )
         .await?
         .into_iter();
+        let blocked = Schedule::blocked_tasks(&self.db_dir).await?;
+        let high_priority_only = self.high_priority_only;
         self.terminal.draw(|f| {
             let splits = Layout::default()
                 .direction(Direction::Vertical)
@@ -143,7 +237,7 @@ impl TimaruTui {
                 info!("{:?}", day_split);
                 f.render_widget(
                     day_schedule
-                        .as_widget_paragraph()
+                        .as_widget_paragraph(high_priority_only, &blocked, None)
                         .block(Block::default().borders(Borders::ALL)),
                     day_split,
                 );
@@ -155,18 +249,24 @@ impl TimaruTui {
 
 __impl_change_mode! {
     key 'd' = TuiMode::Day => day_mode |tui| {
-        let schedules = Schedule::open_range(
+        let schedules = Schedule::read_range(
             &tui.db_dir,
             Local::today(),
             Local::today() + Duration::days(7),
         )
         .await?
         .into_iter();
+        let tasks = Schedule::all_tasks(&tui.db_dir).await?;
+        let blocked = Schedule::blocked_ids(&tasks);
+        let running = running_task(&tasks);
+        let high_priority_only = tui.high_priority_only;
+        let selected = tui.selected;
         tui.terminal.draw(|f| {
+            let area = draw_running(f, running);
             let splits = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
-                .split(f.size());
+                .split(area);
             let mut days_splits = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([
@@ -186,9 +286,11 @@ __impl_change_mode! {
                     ])
                     .split(splits[1]),
             );
-            for (day_split, day_schedule) in days_splits.into_iter().zip(schedules) {
+            for (i, (day_split, day_schedule)) in days_splits.into_iter().zip(schedules).enumerate() {
+                // Only today's tasks can be selected.
+                let selected = if i == 0 { Some(selected) } else { None };
                 let para = day_schedule
-                    .as_widget_paragraph()
+                    .as_widget_paragraph(high_priority_only, &blocked, selected)
                     .block(Block::default().borders(Borders::ALL));
                 f.render_widget(
                     para,
@@ -198,8 +300,79 @@ __impl_change_mode! {
         })?;
         Ok(())
     }
+    key 's' = TuiMode::Stats => stats_mode |tui| {
+        let today = Local::today();
+        let stats = Stats::collect(&tui.db_dir, today - Duration::days(29), today).await?;
+        let finished: Vec<u64> = stats.days.iter().map(|day| day.finished as u64).collect();
+        let week: Vec<(String, u64)> = stats.days[stats.days.len() - 7..]
+            .iter()
+            .map(|day| {
+                let rate = (day.finished * 100).checked_div(day.tasks).unwrap_or(0);
+                (day.date.clone(), rate as u64)
+            })
+            .collect();
+        let pomodoro_ratio = if stats.pomodoros_planned == 0 {
+            0.0
+        } else {
+            (stats.pomodoros_done as f64 / stats.pomodoros_planned as f64).min(1.0)
+        };
+        let running = running_task(&Schedule::all_tasks(&tui.db_dir).await?);
+
+        tui.terminal.draw(|f| {
+            let area = draw_running(f, running);
+            let splits = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Ratio(1, 3),
+                    Constraint::Ratio(1, 2),
+                    Constraint::Length(3),
+                ])
+                .split(area);
+
+            let sparkline = Sparkline::default()
+                .block(
+                    Block::default()
+                        .title("Tasks finished in the last 30 days")
+                        .borders(Borders::ALL),
+                )
+                .style(Style::default().fg(Color::Green))
+                .data(&finished);
+            f.render_widget(sparkline, splits[0]);
+
+            let week: Vec<(&str, u64)> = week
+                .iter()
+                .map(|(day, rate)| (day.as_str(), *rate))
+                .collect();
+            let bar_chart = BarChart::default()
+                .block(
+                    Block::default()
+                        .title("Completion rate (%) in the last 7 days")
+                        .borders(Borders::ALL),
+                )
+                .bar_width(10)
+                .max(100)
+                .data(&week);
+            f.render_widget(bar_chart, splits[1]);
+
+            let gauge = Gauge::default()
+                .block(Block::default().title("Pomodoros done").borders(Borders::ALL))
+                .gauge_style(Style::default().fg(Color::Red))
+                .label(format!(
+                    "{}/{} (current streak: {} days, longest: {} days)",
+                    stats.pomodoros_done,
+                    stats.pomodoros_planned,
+                    stats.current_streak,
+                    stats.longest_streak
+                ))
+                .ratio(pomodoro_ratio);
+            f.render_widget(gauge, splits[2]);
+        })?;
+        Ok(())
+    }
     key 'h' = TuiMode::Empty => empty_mode |tui| {
+        let running = running_task(&Schedule::all_tasks(&tui.db_dir).await?);
         tui.terminal.draw(|f| {
+            let area = draw_running(f, running);
             let table_layout = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([
@@ -207,7 +380,7 @@ __impl_change_mode! {
                     Constraint::Min(25),
                     Constraint::Ratio(1, 10),
                 ])
-                .split(f.size());
+                .split(area);
             let table_layout = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
@@ -222,6 +395,11 @@ __impl_change_mode! {
                 Row::new(vec!["d", "Day Mode"]),
                 Row::new(vec!["w", "Week Mode"]),
                 Row::new(vec!["m", "Month Mode"]),
+                Row::new(vec!["s", "Stats Mode"]),
+                Row::new(vec!["p", "Toggle High Priority Filter"]),
+                Row::new(vec!["j", "Select Next Task of Today"]),
+                Row::new(vec!["k", "Select Previous Task of Today"]),
+                Row::new(vec!["e", "Edit Notes of the Selected Task"]),
             ])
             .header(Row::new(vec!["Key", "Binding"]).bottom_margin(1))
             .widths(&[Constraint::Ratio(1, 3), Constraint::Ratio(2, 3)])
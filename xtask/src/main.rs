@@ -1,3 +1,6 @@
+// `cmd!` expands to a `cfg` only known to rust-analyzer.
+#![allow(unexpected_cfgs)]
+
 use xshell::cmd;
 
 mod flags;